@@ -0,0 +1,193 @@
+//! 磁盘上的 HTTP 缓存：按链接和请求头保存响应内容和验证信息（`ETag`、`Last-Modified`），
+//! 之后的请求带上条件请求头，服务器返回 `304 Not Modified` 时直接使用缓存的内容。
+//!
+//! 同一个链接用不同的 `User-Agent`、认证信息或自定义请求头请求时，服务器可能返回不同的内容，
+//! 所以分开缓存（见 [`cache_key`]）。
+
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::hash::content_hash;
+use crate::writer::{claim_temp_file, replace_file, write_atomic};
+
+/// 默认的缓存文件夹。
+pub const DEFAULT_CACHE_DIR: &str = ".dlconf-cache";
+
+/// 缓存的验证信息，保存在 `<哈希>.json` 中，内容保存在 `<哈希>.body` 中。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheMeta {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+impl CacheMeta {
+    /// 从响应头中取出验证信息。
+    pub fn from_headers(url: &str, headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).map(str::to_string);
+        Self {
+            url: url.to_string(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            content_type: header(CONTENT_TYPE),
+        }
+    }
+
+    /// 条件请求头（`If-None-Match`、`If-Modified-Since`），没有验证信息时为空。
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let mut insert = |name, value: &Option<String>| {
+            if let Some(value) = value.as_deref().and_then(|value| HeaderValue::from_str(value).ok()) {
+                headers.insert(name, value);
+            }
+        };
+        insert(IF_NONE_MATCH, &self.etag);
+        insert(IF_MODIFIED_SINCE, &self.last_modified);
+        headers
+    }
+}
+
+/// 缓存的 key：链接和请求头的哈希。条件请求头和范围请求头不影响响应的内容，不计算在内。
+pub fn cache_key(url: &str, headers: &HeaderMap) -> String {
+    let mut lines: Vec<String> = headers
+        .iter()
+        .filter(|(name, _)| ![IF_NONE_MATCH, IF_MODIFIED_SINCE, RANGE, IF_RANGE].contains(name))
+        .map(|(name, value)| format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect();
+    lines.sort();
+    lines.insert(0, url.to_string());
+    content_hash(lines.join("\n").as_bytes())
+}
+
+/// 按链接和请求头缓存响应内容的文件夹。
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// 查找 `key`（见 [`cache_key`]）对应的缓存，返回验证信息和保存内容的文件；
+    /// 没有缓存（或缓存不完整）时返回 `None`。
+    pub fn lookup(&self, key: &str, url: &str) -> Option<(CacheMeta, PathBuf)> {
+        let (meta_path, body_path) = self.paths(key);
+        let meta: CacheMeta = serde_json::from_str(&fs::read_to_string(meta_path).ok()?).ok()?;
+        // 哈希相同但链接不同的情况几乎不会出现，还是检查一下
+        if meta.url != url || !body_path.is_file() {
+            return None;
+        }
+        Some((meta, body_path))
+    }
+
+    /// 保存 `key` 对应的内容（从已经下载好的文件复制）和验证信息。
+    pub fn store_file(&self, key: &str, meta: &CacheMeta, file: &Path) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let (meta_path, body_path) = self.paths(key);
+        // 内容先复制到临时文件再重命名，同时读取缓存的任务不会读到写了一半的内容
+        let temp_file = claim_temp_file(&self.dir, &format!("{}.body", key))?;
+        if let Err(err) = fs::copy(file, &temp_file) {
+            let _ = fs::remove_file(&temp_file);
+            return Err(err.into());
+        }
+        // 替换内容之前先删除旧的验证信息，中途失败时只是没有缓存，不会留下与内容不对应的验证信息
+        match fs::remove_file(&meta_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                let _ = fs::remove_file(&temp_file);
+                return Err(err.into());
+            }
+            _ => {}
+        }
+        if let Err(err) = replace_file(&body_path, &temp_file) {
+            let _ = fs::remove_file(&temp_file);
+            return Err(err);
+        }
+        let meta = serde_json::to_string_pretty(meta).map_err(io::Error::from)?;
+        write_atomic(&meta_path, meta.as_bytes())?;
+        Ok(())
+    }
+
+    fn paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        (self.dir.join(format!("{}.json", key)), self.dir.join(format!("{}.body", key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{AUTHORIZATION, USER_AGENT};
+
+    use super::*;
+
+    // 通过（下载好的）文件保存缓存
+    fn store(cache: &HttpCache, key: &str, meta: &CacheMeta, body: &[u8]) {
+        let file = cache.dir.join(format!("download-{}", key));
+        fs::create_dir_all(&cache.dir).unwrap();
+        fs::write(&file, body).unwrap();
+        cache.store_file(key, meta, &file).unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    fn headers(pairs: &[(reqwest::header::HeaderName, &str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap())).collect()
+    }
+
+    #[test]
+    fn key_depends_on_request_headers() {
+        let url = "https://x.example/sub";
+        let clash = cache_key(url, &headers(&[(USER_AGENT, "clash")]));
+        assert_ne!(clash, cache_key(url, &headers(&[(USER_AGENT, "v2rayN")])));
+        assert_ne!(clash, cache_key(url, &headers(&[(USER_AGENT, "clash"), (AUTHORIZATION, "Bearer t")])));
+        assert_ne!(clash, cache_key("https://x.example/other", &headers(&[(USER_AGENT, "clash")])));
+    }
+
+    #[test]
+    fn key_ignores_conditional_headers_and_order() {
+        let url = "https://x.example/sub";
+        let key = cache_key(url, &headers(&[(USER_AGENT, "clash"), (AUTHORIZATION, "Bearer t")]));
+        let conditional = headers(&[(AUTHORIZATION, "Bearer t"), (IF_NONE_MATCH, "\"v1\""), (USER_AGENT, "clash")]);
+        assert_eq!(key, cache_key(url, &conditional));
+    }
+
+    #[test]
+    fn entries_with_different_keys_are_separate() {
+        let dir = std::env::temp_dir().join(format!("dlconf-cache-test-{}", std::process::id()));
+        let cache = HttpCache::new(&dir);
+        let meta = CacheMeta { url: "https://x.example/sub".to_string(), ..CacheMeta::default() };
+        store(&cache, "a", &meta, b"clash");
+        store(&cache, "b", &meta, b"v2rayN");
+        let (_, body) = cache.lookup("a", &meta.url).unwrap();
+        assert_eq!(fs::read(body).unwrap(), b"clash");
+        assert!(cache.lookup("c", &meta.url).is_none());
+        assert!(cache.lookup("a", "https://x.example/other").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn store_replaces_entry_without_leaving_temp_files() {
+        let dir = std::env::temp_dir().join(format!("dlconf-cache-replace-{}", std::process::id()));
+        let cache = HttpCache::new(&dir);
+        let url = "https://x.example/sub".to_string();
+        let old = CacheMeta { url, etag: Some("\"v1\"".to_string()), ..CacheMeta::default() };
+        let new = CacheMeta { etag: Some("\"v2\"".to_string()), ..old.clone() };
+        store(&cache, "a", &old, b"old");
+        store(&cache, "a", &new, b"new");
+
+        let (meta, body) = cache.lookup("a", &old.url).unwrap();
+        assert_eq!(meta.etag, new.etag);
+        assert_eq!(fs::read(body).unwrap(), b"new");
+        let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["a.body", "a.json"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+//! 字符集检测：按来源指定的字符集、BOM 或 `Content-Type` 中的 `charset` 解码文本，
+//! 都没有时按 UTF-8 解码。
+
+use encoding::all::{UTF_16BE, UTF_16LE, UTF_8};
+use encoding::label::encoding_from_whatwg_label;
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::error::{Error, Result};
+
+/// 解码后的文本。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    /// 实际使用的字符集。
+    pub charset: String,
+    /// 是否有无法解码的字节被替换成了 `U+FFFD`。
+    pub lossy: bool,
+}
+
+/// 解码文本。
+///
+/// 字符集的优先级：`charset_override`（来源指定）> BOM > `Content-Type` 中的 `charset` > UTF-8。
+/// BOM 会被去掉；`Content-Type` 中的字符集无法识别时按 UTF-8 解码，
+/// `charset_override` 无法识别时返回 [`Error::Decode`]。
+pub fn decode_text(bytes: &[u8], content_type: Option<&str>, charset_override: Option<&str>) -> Result<Decoded> {
+    let (bom_encoding, without_bom) = sniff_bom(bytes);
+    let encoding = match charset_override {
+        Some(label) => {
+            encoding_from_whatwg_label(label).ok_or_else(|| Error::Decode(format!("不支持的字符集：{}", label)))?
+        }
+        None => bom_encoding
+            .or_else(|| content_type.and_then(charset_param).and_then(encoding_from_whatwg_label))
+            .unwrap_or(UTF_8),
+    };
+    // 只有 BOM 与实际使用的字符集一致时才去掉 BOM
+    let body = match bom_encoding {
+        Some(bom) if bom.name() == encoding.name() => without_bom,
+        _ => bytes,
+    };
+
+    let charset = encoding.whatwg_name().unwrap_or(encoding.name()).to_string();
+    match encoding.decode(body, DecoderTrap::Strict) {
+        Ok(text) => Ok(Decoded { text, charset, lossy: false }),
+        Err(_) => {
+            let text = encoding.decode(body, DecoderTrap::Replace).map_err(|err| Error::Decode(err.into_owned()))?;
+            Ok(Decoded { text, charset, lossy: true })
+        }
+    }
+}
+
+// 根据 BOM 判断字符集，返回字符集和去掉 BOM 后的内容
+fn sniff_bom(bytes: &[u8]) -> (Option<EncodingRef>, &[u8]) {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        (Some(UTF_8), rest)
+    } else if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        (Some(UTF_16LE), rest)
+    } else if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        (Some(UTF_16BE), rest)
+    } else {
+        (None, bytes)
+    }
+}
+
+// 取出 `Content-Type` 中的 `charset` 参数，例如 `text/plain; charset=gbk` 中的 `gbk`
+fn charset_param(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim().eq_ignore_ascii_case("charset").then(|| value.trim().trim_matches('"'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // “中文”的 GBK 编码
+    const GBK: &[u8] = b"\xD6\xD0\xCE\xC4";
+
+    #[test]
+    fn defaults_to_utf8() {
+        let decoded = decode_text("中文".as_bytes(), None, None).unwrap();
+        assert_eq!(decoded, Decoded { text: "中文".to_string(), charset: "utf-8".to_string(), lossy: false });
+    }
+
+    #[test]
+    fn uses_content_type_charset() {
+        let decoded = decode_text(GBK, Some("text/plain; charset=\"GBK\""), None).unwrap();
+        assert_eq!((decoded.text.as_str(), decoded.charset.as_str(), decoded.lossy), ("中文", "gbk", false));
+        // 无法识别的字符集按 UTF-8 解码
+        assert_eq!(decode_text(b"abc", Some("text/plain; charset=nope"), None).unwrap().charset, "utf-8");
+    }
+
+    #[test]
+    fn bom_wins_over_content_type() {
+        let mut bytes = b"\xEF\xBB\xBF".to_vec();
+        bytes.extend_from_slice("中文".as_bytes());
+        let decoded = decode_text(&bytes, Some("text/plain; charset=gbk"), None).unwrap();
+        assert_eq!((decoded.text.as_str(), decoded.charset.as_str()), ("中文", "utf-8"));
+
+        let decoded = decode_text(b"\xFF\xFEa\x00b\x00", Some("text/plain; charset=utf-8"), None).unwrap();
+        assert_eq!((decoded.text.as_str(), decoded.charset.as_str()), ("ab", "utf-16le"));
+    }
+
+    #[test]
+    fn override_wins_over_bom_and_content_type() {
+        let decoded = decode_text(GBK, Some("text/plain; charset=utf-8"), Some("gb2312")).unwrap();
+        assert_eq!((decoded.text.as_str(), decoded.lossy), ("中文", false));
+        // 与实际字符集不一致的 BOM 不去掉
+        let decoded = decode_text(b"\xEF\xBB\xBFabc", None, Some("latin1")).unwrap();
+        assert_eq!(decoded.text, "\u{EF}\u{BB}\u{BF}abc");
+        assert!(matches!(decode_text(b"abc", None, Some("nope")), Err(Error::Decode(_))));
+    }
+
+    #[test]
+    fn replaces_invalid_bytes() {
+        let decoded = decode_text(b"ab\xFFc", None, None).unwrap();
+        assert_eq!(decoded, Decoded { text: "ab\u{FFFD}c".to_string(), charset: "utf-8".to_string(), lossy: true });
+    }
+}
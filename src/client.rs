@@ -0,0 +1,478 @@
+//! 在所有请求之间共享的 HTTP 客户端。
+//!
+//! 同一个 [`HttpClient`] 复用连接池，访问同一个镜像站点（`gitlab.com`、`fastly.jsdelivr.net` 等）
+//! 时不必每次都重新建立 TCP 连接和 TLS 握手。
+
+use hyper::client::connect::HttpInfo;
+use reqwest::header::USER_AGENT;
+use reqwest::{Client, Request, RequestBuilder, Response, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cache::{cache_key, CacheMeta, HttpCache, DEFAULT_CACHE_DIR};
+use crate::error::{Error, Result};
+use crate::limiter::{host_key, Limiter, Permit};
+use crate::manifest::Source;
+use crate::proxy::{self, should_fall_back, ProxyMode, ProxyRoute, ProxyUrl};
+use crate::retry::RetryPolicy;
+use crate::secrets::Secrets;
+
+/// 默认的 User-Agent。
+pub const DEFAULT_USER_AGENT: &str = concat!("dlconf/", env!("CARGO_PKG_VERSION"));
+
+/// 使用的 HTTP 版本。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HttpVersion {
+    /// 通过 ALPN 协商，服务器支持时优先使用 HTTP/2
+    #[default]
+    Auto,
+    /// 只使用 HTTP/1.1
+    Http1,
+    /// 直接使用 HTTP/2（服务器必须支持）
+    Http2,
+}
+
+impl FromStr for HttpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "1" | "1.1" | "http1" => Ok(Self::Http1),
+            "2" | "http2" => Ok(Self::Http2),
+            other => Err(format!("不支持的 HTTP 版本：{}（可选 auto / 1 / 2）", other)),
+        }
+    }
+}
+
+/// HTTP 客户端的配置。
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// 每个站点最多保留的空闲连接数。
+    pub pool_max_idle_per_host: usize,
+    /// 空闲连接的保留时间（keep-alive）。
+    pub pool_idle_timeout: Duration,
+    /// TCP keep-alive 的间隔，`None` 表示不开启。
+    pub tcp_keepalive: Option<Duration>,
+    pub http_version: HttpVersion,
+    /// 默认的 User-Agent，来源自己的请求头可以覆盖。
+    pub user_agent: String,
+    /// 所有请求共用的重试策略。
+    pub retry: RetryPolicy,
+    /// 全局最多同时进行的下载数，0 表示不限制。
+    pub max_concurrency: usize,
+    /// 每个站点最多同时进行的下载数，0 表示不限制。
+    pub max_per_host: usize,
+    /// HTTP 缓存的文件夹，`None` 表示不使用缓存。
+    pub cache_dir: Option<PathBuf>,
+    /// 离线模式：不发送任何请求，只使用缓存的内容。
+    pub offline: bool,
+    /// 响应内容的大小上限（字节），`None` 表示不限制；来源可以单独指定。
+    pub max_body_size: Option<u64>,
+    /// 全局的上游代理，来源可以单独指定。
+    pub proxy: Option<ProxyUrl>,
+    /// 全局的代理使用方式，来源可以单独指定。
+    pub proxy_mode: ProxyMode,
+    /// 密钥文件，`None` 表示只能引用环境变量中的密钥。
+    pub secrets_file: Option<PathBuf>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            http_version: HttpVersion::Auto,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            retry: RetryPolicy::default(),
+            max_concurrency: 16,
+            max_per_host: 4,
+            cache_dir: Some(PathBuf::from(DEFAULT_CACHE_DIR)),
+            offline: false,
+            max_body_size: Some(256 * 1024 * 1024),
+            proxy: None,
+            proxy_mode: ProxyMode::Always,
+            secrets_file: None,
+        }
+    }
+}
+
+/// 共享的 HTTP 客户端，克隆后仍然共用同一个连接池和统计数据。
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    // 直连的客户端
+    client: Client,
+    // 本客户端的请求走的代理，`None` 表示直连
+    route: Option<ProxyRoute>,
+    proxies: Arc<ProxyClients>,
+    config: Arc<ClientConfig>,
+    retry: RetryPolicy,
+    limiter: Arc<Limiter>,
+    cache: Option<HttpCache>,
+    offline: bool,
+    max_body_size: Option<u64>,
+    secrets: Arc<Secrets>,
+    stats: Arc<Mutex<Stats>>,
+}
+
+// 各个代理对应的客户端（按需创建，同一个代理共用连接池），以及直连失败过的站点
+#[derive(Debug, Default)]
+struct ProxyClients {
+    clients: Mutex<HashMap<String, Client>>,
+    direct_failed: Mutex<HashSet<String>>,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    requests: usize,
+    // 每个 TCP 连接的本地地址（端口）都不同，不同地址的数量就是新建连接（握手）的次数
+    local_addrs: HashSet<SocketAddr>,
+    cache_hits: usize,
+}
+
+/// [`HttpClient::fetch_to_file`] 下载到的内容。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fetched {
+    /// 响应（或缓存）的 `Content-Type`。
+    pub content_type: Option<String>,
+    /// 内容的字节数。
+    pub len: u64,
+}
+
+// 可以直接使用的缓存，或者服务器返回的新内容
+enum Revalidated {
+    // 离线模式，或者服务器返回 304：缓存的验证信息和保存内容的文件
+    Cached(CacheMeta, PathBuf),
+    // 状态码 200 的响应，以及请求的链接和缓存的 key
+    Fresh { response: Response, url: String, key: String },
+}
+
+/// 连接复用的统计。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// 收到响应的请求数。
+    pub requests: usize,
+    /// 新建的连接数（即 TCP/TLS 握手次数）。
+    pub connections: usize,
+    /// 使用缓存内容的次数（`304 Not Modified`，或离线模式）。
+    pub cache_hits: usize,
+}
+
+impl ConnectionStats {
+    /// 复用已有连接的请求数。
+    pub fn reused(&self) -> usize {
+        self.requests.saturating_sub(self.connections)
+    }
+}
+
+impl HttpClient {
+    pub fn new(config: &ClientConfig) -> Result<Self> {
+        let client = Self {
+            client: build_client(config, None)?,
+            route: proxy::resolve(config.proxy.as_ref(), config.proxy_mode, None, None),
+            proxies: Arc::default(),
+            config: Arc::new(config.clone()),
+            retry: config.retry.clone(),
+            limiter: Arc::new(Limiter::new(config.max_concurrency, config.max_per_host)),
+            cache: config.cache_dir.as_ref().map(HttpCache::new),
+            offline: config.offline,
+            max_body_size: config.max_body_size,
+            secrets: Arc::new(match &config.secrets_file {
+                Some(path) => Secrets::load(path)?,
+                None => Secrets::default(),
+            }),
+            stats: Arc::default(),
+        };
+        // 全局代理的地址有误时尽早报错
+        if let Some(route) = &client.route {
+            client.proxied(&route.url)?;
+        }
+        Ok(client)
+    }
+
+    /// 按来源的代理设置（没有时使用全局设置）发送请求的客户端，与原客户端共用连接池和统计数据。
+    pub fn for_source(&self, source: &Source) -> Self {
+        let config = &self.config;
+        let route = proxy::resolve(config.proxy.as_ref(), config.proxy_mode, source.proxy.as_ref(), source.proxy_mode);
+        Self { route, ..self.clone() }
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn head(&self, url: &str) -> RequestBuilder {
+        self.client.head(url)
+    }
+
+    /// 等待 `url` 所在站点和全局都有空余的下载名额。
+    ///
+    /// 名额在返回值 drop 时释放，调用方应该一直持有到读完响应内容。
+    pub async fn acquire(&self, url: &str) -> Permit {
+        self.limiter.acquire(url).await
+    }
+
+    /// 发送请求，并记录连接的使用情况。
+    ///
+    /// 网络错误、超时和可重试的状态码按重试策略重试；重试用完后，
+    /// 可重试状态码的响应原样返回。
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        self.send_request(request.build()?).await
+    }
+
+    async fn send_request(&self, request: Request) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            // 请求体不能克隆（流式请求体）时，只发送一次
+            let Some(this_attempt) = request.try_clone() else {
+                return self.execute(request).await;
+            };
+            let (err, response) = match self.execute(this_attempt).await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => (Error::from_status(&response), Some(response)),
+                Err(err) => (err, None),
+            };
+            let Some(delay) = self.retry.next_delay(attempt, &err) else {
+                return response.ok_or(err);
+            };
+            eprintln!("  - {} {}，{:?} 后重试（第 {} 次）", request.url(), err, delay, attempt);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// 只发送一次请求（不重试），调用方自己按重试策略重试时使用。
+    pub(crate) async fn send_once(&self, request: Request) -> Result<Response> {
+        self.execute(request).await
+    }
+
+    /// 请求头和认证信息中引用的密钥。
+    pub fn secrets(&self) -> &Secrets {
+        &self.secrets
+    }
+
+    /// 是否为离线模式。
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// 发送（GET）请求，边下载边把内容写入文件 `path`（不解码，也不会把整个内容读入内存），
+    /// 返回 `Content-Type` 和字节数；状态码不是 200 时返回 [`Error::HttpStatus`]。
+    ///
+    /// 开启缓存时带上条件请求头，服务器返回 `304 Not Modified` 时使用缓存的内容；
+    /// 离线模式下不发送请求，只使用缓存的内容，没有缓存时返回 [`Error::NotCached`]。
+    /// 内容超过 `max_size`（不指定时为客户端的上限）时中止下载，返回 [`Error::TooLarge`]。
+    /// 按重试策略重试的是整个下载（请求和读取内容），读取内容时连接被重置也会重试，
+    /// 重试时从头重新写入 `path`。
+    ///
+    /// 下载中途失败（包括超过大小上限）时 `path` 中可能留下不完整的内容，由调用方删除。
+    pub async fn fetch_to_file(&self, request: RequestBuilder, path: &Path, max_size: Option<u64>) -> Result<Fetched> {
+        let request = request.build()?;
+        self.retrying(request, |request| self.fetch_to_file_once(request, path, max_size)).await
+    }
+
+    async fn fetch_to_file_once(&self, request: Request, path: &Path, max_size: Option<u64>) -> Result<Fetched> {
+        match self.revalidate(request).await? {
+            Revalidated::Cached(meta, body_path) => {
+                let len = fs::copy(body_path, path)?;
+                Ok(Fetched { content_type: meta.content_type, len })
+            }
+            Revalidated::Fresh { mut response, url, key } => {
+                let limit = self.body_limit(max_size);
+                let meta = CacheMeta::from_headers(&url, response.headers());
+                check_size(response.content_length().unwrap_or(0), limit)?;
+                let mut file = File::create(path)?;
+                let mut len = 0;
+                while let Some(chunk) = response.chunk().await? {
+                    len += chunk.len() as u64;
+                    check_size(len, limit)?;
+                    file.write_all(&chunk)?;
+                }
+                self.store_file_in_cache(&key, &meta, path);
+                Ok(Fetched { content_type: meta.content_type, len })
+            }
+        }
+    }
+
+    // 按重试策略执行 `fetch`（每次传入请求的副本），直到成功或者不再重试；
+    // 请求体不能克隆（流式请求体）时只执行一次
+    async fn retrying<T, F>(&self, request: Request, mut fetch: impl FnMut(Request) -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let Some(this_attempt) = request.try_clone() else {
+                return fetch(request).await;
+            };
+            let err = match fetch(this_attempt).await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let Some(delay) = self.retry.next_delay(attempt, &err) else {
+                return Err(err);
+            };
+            eprintln!("  - {} {}，{:?} 后重试（第 {} 次）", request.url(), err, delay, attempt);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    // 请求对应的缓存的 key：链接和请求头，没有单独指定 `User-Agent` 时加上客户端默认的 `User-Agent`
+    pub(crate) fn cache_key(&self, request: &Request) -> String {
+        let mut headers = request.headers().clone();
+        if let (false, Ok(user_agent)) = (headers.contains_key(USER_AGENT), self.config.user_agent.parse()) {
+            headers.insert(USER_AGENT, user_agent);
+        }
+        cache_key(request.url().as_str(), &headers)
+    }
+
+    // 开启缓存时，缓存中 `key` 对应的验证信息和保存内容的文件
+    pub(crate) fn cached(&self, key: &str, url: &str) -> Option<(CacheMeta, PathBuf)> {
+        self.cache.as_ref().and_then(|cache| cache.lookup(key, url))
+    }
+
+    // 记录一次使用了缓存的内容
+    pub(crate) fn record_cache_hit(&self) {
+        self.stats.lock().unwrap().cache_hits += 1;
+    }
+
+    // 来源单独指定的大小上限，或者客户端的上限
+    pub(crate) fn body_limit(&self, max_size: Option<u64>) -> Option<u64> {
+        max_size.or(self.max_body_size)
+    }
+
+    pub(crate) fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    // 开启缓存时，把已经下载好的文件保存到缓存中（失败时只打印警告）
+    pub(crate) fn store_file_in_cache(&self, key: &str, meta: &CacheMeta, path: &Path) {
+        if let Some(cache) = &self.cache {
+            cache.store_file(key, meta, path).unwrap_or_else(|err| eprintln!("  - 写入缓存失败：{}", err));
+        }
+    }
+
+    // 查找缓存并发送（一次）条件请求，得到可以直接使用的缓存，或者服务器返回的新内容
+    async fn revalidate(&self, mut request: Request) -> Result<Revalidated> {
+        let url = request.url().to_string();
+        let key = self.cache_key(&request);
+        let cached = self.cached(&key, &url);
+        if self.offline {
+            let (meta, body_path) = cached.ok_or(Error::NotCached)?;
+            self.record_cache_hit();
+            return Ok(Revalidated::Cached(meta, body_path));
+        }
+        if let Some((meta, _)) = &cached {
+            request.headers_mut().extend(meta.conditional_headers());
+        }
+
+        let response = self.execute(request).await?;
+        if let (StatusCode::NOT_MODIFIED, Some((meta, body_path))) = (response.status(), cached) {
+            self.record_cache_hit();
+            return Ok(Revalidated::Cached(meta, body_path));
+        }
+        Ok(Revalidated::Fresh { response: check_ok(response)?, url, key })
+    }
+
+    async fn execute(&self, request: Request) -> Result<Response> {
+        let response = match &self.route {
+            Some(route) => self.execute_via(route, request).await?,
+            None => self.client.execute(request).await?,
+        };
+        let mut stats = self.stats.lock().unwrap();
+        stats.requests += 1;
+        if let Some(info) = response.extensions().get::<HttpInfo>() {
+            stats.local_addrs.insert(info.local_addr());
+        }
+        Ok(response)
+    }
+
+    // 通过代理发送请求；回退模式下先直连，连接失败或超时时再走代理
+    async fn execute_via(&self, route: &ProxyRoute, request: Request) -> Result<Response> {
+        let host = host_key(request.url().as_str());
+        let direct_failed = self.proxies.direct_failed.lock().unwrap().contains(&host);
+        if route.mode == ProxyMode::Fallback && !direct_failed {
+            if let Some(direct) = request.try_clone() {
+                match self.client.execute(direct).await {
+                    Err(err) if should_fall_back(&err) => {
+                        eprintln!("  - {} 直连失败（{}），改用代理 {}", host, err, route.url);
+                        self.proxies.direct_failed.lock().unwrap().insert(host);
+                    }
+                    result => return Ok(result?),
+                }
+            }
+        }
+        Ok(self.proxied(&route.url)?.execute(request).await?)
+    }
+
+    // 走代理 `url` 的客户端，第一次使用时创建
+    fn proxied(&self, url: &ProxyUrl) -> Result<Client> {
+        let mut clients = self.proxies.clients.lock().unwrap();
+        if let Some(client) = clients.get(url.as_str()) {
+            return Ok(client.clone());
+        }
+        let client = build_client(&self.config, Some(url))?;
+        clients.insert(url.as_str().to_string(), client.clone());
+        Ok(client)
+    }
+
+    /// 到目前为止的连接复用统计。
+    pub fn stats(&self) -> ConnectionStats {
+        let stats = self.stats.lock().unwrap();
+        ConnectionStats {
+            requests: stats.requests,
+            connections: stats.local_addrs.len(),
+            cache_hits: stats.cache_hits,
+        }
+    }
+}
+
+// 按配置创建底层的客户端，`proxy` 为 `None` 时直连（仍然遵守 `HTTPS_PROXY` 等环境变量）
+fn build_client(config: &ClientConfig, proxy: Option<&ProxyUrl>) -> Result<Client> {
+    let mut builder = Client::builder()
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(config.pool_idle_timeout)
+        .tcp_keepalive(config.tcp_keepalive)
+        .user_agent(config.user_agent.as_str());
+    builder = match config.http_version {
+        HttpVersion::Auto => builder,
+        HttpVersion::Http1 => builder.http1_only(),
+        HttpVersion::Http2 => builder.http2_prior_knowledge(),
+    };
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.to_proxy()?);
+    }
+    Ok(builder.build()?)
+}
+
+// 状态码不是 200 时返回错误
+fn check_ok(response: Response) -> Result<Response> {
+    if response.status() != StatusCode::OK {
+        return Err(Error::from_status(&response));
+    }
+    Ok(response)
+}
+
+// 已经收到（或者声明要发送）的内容超过上限时返回错误
+pub(crate) fn check_size(len: u64, limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(limit) if len > limit => Err(Error::TooLarge { limit }),
+        _ => Ok(()),
+    }
+}
+
+impl Default for HttpClient {
+    /// 使用默认配置。与 `reqwest::Client::new` 一样，TLS 后端初始化失败时会 panic。
+    fn default() -> Self {
+        Self::new(&ClientConfig::default()).expect("创建 HTTP 客户端失败")
+    }
+}
@@ -0,0 +1,309 @@
+//! `dlconf` 各子命令的实现（app1 ~ app5 也直接调用它们）。
+
+use futures::future::join_all;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::client::HttpClient;
+use crate::dedup::DedupOptions;
+use crate::error::{Error, Result};
+use crate::health::HealthStore;
+use crate::fetcher::{download_best, download_url, BestOptions, BestStrategy};
+use crate::manifest::{
+    active_sources, load_manifest, read_flat_json_file, read_url_list, update_flat_json_file,
+    FlatManifest, ManifestFormat, Source,
+};
+use crate::numbering::{Numbering, NumberingOrder};
+use crate::pipeline::download_and_process_data;
+use crate::probe::ProbeMode;
+use crate::report::{ItemReport, RunReport};
+use crate::writer::{create_directory_if_not_exists, save_successful_urls, write_contents};
+
+/// `fetch-list`：并发下载文本文件中的所有链接，保存到 `output` 文件夹。
+///
+/// 成功的链接写入 `output/valid_url.txt`。
+pub async fn fetch_list(client: &HttpClient, input: &str, output: &str) -> Result<RunReport> {
+    // 检查是否存在输入文件
+    if !Path::new(input).exists() {
+        return Err(Error::manifest(format!("{} 文件不存在...", input)));
+    }
+
+    let start = Instant::now();
+    let urls = read_url_list(input)?;
+    if urls.is_empty() {
+        println!("{} 文件为空！", input);
+        return Ok(RunReport::default());
+    }
+
+    // 重复的链接只下载一次；所有链接并发下载，受客户端的并发限制
+    let mut seen = HashSet::new();
+    let tasks = urls.iter().filter(|url| seen.insert(url.as_str())).map(|url| async move {
+        let mut item = ItemReport::new(url);
+        match download_url(client, url, output).await {
+            Ok((file_name, outcome)) => {
+                println!("{} 下载成功！", url);
+                item.record_ok(url);
+                item.record_written(&file_name, outcome);
+            }
+            Err(e) => {
+                println!("GET {} 失败: {}，跳过", url, e);
+                item.record_err(url, e);
+            }
+        }
+        item
+    });
+
+    let mut report = RunReport::default();
+    let mut successful_urls = Vec::new();
+    for item in join_all(tasks).await {
+        if item.is_ok() {
+            successful_urls.push(item.name.clone());
+        }
+        report.push(item);
+    }
+
+    // 保存成功的链接到文件
+    if !successful_urls.is_empty() {
+        save_successful_urls(&successful_urls, output)?;
+        println!("\n当前有效的链接已经写入文件：{}/valid_url.txt ", output);
+    }
+
+    println!("所有下载任务已经完成！耗时：{:?}\n", start.elapsed());
+    report.connections = client.stats();
+    Ok(report)
+}
+
+/// `add`：将文本文件中的所有链接，写入一层结构 JSON 清单的 `key` 键中。
+///
+/// 没有指定 `key` 时，交互模式下从标准输入读取，非交互模式下返回错误。
+pub fn add(
+    input: &str,
+    manifest: &str,
+    key: Option<&str>,
+    interactive: bool,
+) -> Result<RunReport> {
+    let split_symbol: String = "-".repeat(105);
+
+    // 创建或初始化输入文件和输出文件
+    create_or_initialize_file(input, b"")?;
+    create_or_initialize_file(manifest, b"{}")?;
+
+    let urls = read_url_list(input)?;
+    if urls.is_empty() {
+        return Err(Error::manifest(format!("{} 未读取到任何内容。", input)));
+    }
+    print_urls(&urls, &split_symbol, input);
+
+    let json_data = read_flat_json_file(manifest)?;
+    print_json_data(&json_data, &split_symbol, manifest);
+
+    let update_key = match key {
+        Some(key) => key.trim().to_string(),
+        None if interactive => prompt_key()?,
+        None => return Err(Error::manifest("非交互模式下必须指定 key 键名（--key）")),
+    };
+    if update_key.is_empty() {
+        return Err(Error::manifest("key键名不能为空"));
+    }
+
+    // 更新 JSON 文件
+    update_flat_json_file(manifest, &update_key, urls)?;
+
+    println!("{}", split_symbol);
+    println!(
+        "成功将{}文件中的所有链接，添加到JSON文件的\"{}\"键中。",
+        input, update_key
+    );
+    println!("{}", split_symbol);
+    Ok(RunReport::default())
+}
+
+/// `best`：对清单中的每个 key，按 `strategy` 找出可用的链接下载，下载前按 `probe` 预检。
+///
+/// 跳过所有来源都已停用的 key。镜像按 `health` 中的历史表现排序，
+/// 本次的结果（以及各站点支持的预检方式）再记录到 `health` 中。
+pub async fn best(
+    client: &HttpClient,
+    manifest: &str,
+    format: Option<ManifestFormat>,
+    output: &str,
+    strategy: BestStrategy,
+    probe: ProbeMode,
+    health: &mut HealthStore,
+) -> Result<RunReport> {
+    let start = Instant::now();
+    println!("解析{}文件中...", manifest);
+    let my_dict = load_manifest(manifest, format)?;
+    println!("开始寻找合适的网络线路下载...");
+
+    // 所有任务并发下载，受客户端的并发限制
+    let history: &HealthStore = health;
+    let options = BestOptions { strategy, probe, probes: history.probes() };
+    let options = &options;
+    let tasks = my_dict.sections.iter().flat_map(|(data_file, value)| {
+        value.iter().filter_map(move |(task_name, sources)| {
+            let sources = history.order(enabled_sources(task_name, sources)?);
+            Some(best_item(client, task_name, sources, data_file, output, options))
+        })
+    });
+    let mut report = RunReport::default();
+    join_all(tasks).await.into_iter().for_each(|item| report.push(item));
+    health.record_report(&report);
+
+    println!("所有下载任务完成！耗时：{:?}", start.elapsed());
+    report.connections = client.stats();
+    Ok(report)
+}
+
+/// `fetch-all`：下载清单中的所有链接，去重后写入 `output` 文件夹。
+///
+/// 去重时忽略清单中 `dedup.ignore` 和 `ignore_paths` 列出的路径。
+/// 同一个 key 有多份内容时，之前的内容保留原来的编号，新的内容按 `order` 编号（记录在 `output` 中）。
+/// 跳过所有来源都已停用的 key，以及 `health` 中长期失效的镜像，本次的结果再记录到 `health` 中。
+pub async fn fetch_all(
+    client: &HttpClient,
+    manifest: &str,
+    format: Option<ManifestFormat>,
+    output: &str,
+    ignore_paths: &[String],
+    order: NumberingOrder,
+    health: &mut HealthStore,
+) -> Result<RunReport> {
+    let my_dict = load_manifest(manifest, format)?;
+    let mut dedup = my_dict.dedup.clone();
+    dedup.ignore.extend_from_slice(ignore_paths);
+    let dedup = &dedup;
+
+    // 检查文件夹是否存在，不存在就创建
+    create_directory_if_not_exists(output)?;
+    let numbering = &Numbering::load(output, order);
+
+    // 遍历最外层的key-value，再遍历字段里面的key-vlaue（第2层），所有 key 并发处理
+    let history: &HealthStore = health;
+    let tasks = my_dict.sections.iter().flat_map(|(data_file, value)| {
+        value.iter().filter_map(move |(inner_key, sources)| {
+            // 保持清单中的顺序（编号和去重时保留哪个来源的内容都按这个顺序），只去掉长期失效的镜像
+            let sources = history.skip_dead(enabled_sources(inner_key, sources)?);
+            Some(fetch_all_item(client, inner_key, sources, data_file, output, dedup, numbering))
+        })
+    });
+    let mut report = RunReport::default();
+    join_all(tasks).await.into_iter().for_each(|item| report.push(item));
+    health.record_report(&report);
+    numbering.save().unwrap_or_else(|err| eprintln!("保存编号失败：{}", err));
+
+    println!();
+    report.connections = client.stats();
+    Ok(report)
+}
+
+// 启用的来源；所有来源都已停用时跳过这个 key（不算失败），返回 `None`
+fn enabled_sources<'a>(name: &str, sources: &'a [Source]) -> Option<Vec<&'a Source>> {
+    let sources = active_sources(sources);
+    if sources.is_empty() {
+        println!("跳过 {}：所有来源都已停用", name);
+        return None;
+    }
+    Some(sources)
+}
+
+// `best` 中的一个任务：找出可用的链接下载
+async fn best_item(
+    client: &HttpClient,
+    task_name: &str,
+    sources: Vec<&Source>,
+    data_file: &str,
+    output: &str,
+    options: &BestOptions<'_>,
+) -> ItemReport {
+    let mut item = ItemReport::new(task_name);
+    match download_best(client, task_name, &sources, data_file, output, options, &mut item).await {
+        Some(url) => println!("{} {} 下载完成！", url, task_name),
+        None => eprintln!("{} 下载失败", task_name),
+    }
+    item
+}
+
+// `fetch-all` 中的一个 key：下载所有链接，去重后写入文件
+async fn fetch_all_item(
+    client: &HttpClient,
+    inner_key: &str,
+    sources: Vec<&Source>,
+    data_file: &str,
+    output: &str,
+    dedup: &DedupOptions,
+    numbering: &Numbering,
+) -> ItemReport {
+    let mut item = ItemReport::new(inner_key);
+    let unique_contents =
+        download_and_process_data(client, &sources, inner_key, data_file, output, dedup, &mut item).await;
+    println!(
+        "{}配置文件，有{}个不相同的，准备将不相同的数据写入文件中...",
+        inner_key,
+        unique_contents.len()
+    );
+    // 将数据写入文件（不同的数据，用不同的文件存储）
+    if let Err(err) = write_contents(&unique_contents, output, inner_key, data_file, numbering, &mut item) {
+        eprintln!("  - {}", err);
+        unique_contents.discard_files();
+        item.fail(err);
+    }
+    item
+}
+
+/// `health`：打印镜像健康度表。
+pub fn health(health: &HealthStore) -> Result<RunReport> {
+    health.print_table();
+    Ok(RunReport::default())
+}
+
+// 创建或初始化文件，如果文件不存在则创建并写入初始内容
+fn create_or_initialize_file(file_path: &str, initial_content: &[u8]) -> Result<()> {
+    if !Path::new(file_path).exists() {
+        println!("文件不存在，将创建并写入初始内容：{}", file_path);
+        fs::write(file_path, initial_content)?;
+    }
+    Ok(())
+}
+
+// 打印待更新的链接
+fn print_urls(urls: &[String], split_symbol: &str, input_file: &str) {
+    println!("待更新的链接(\"{}\"文件)：", input_file);
+    println!("{}", split_symbol);
+    urls.iter().for_each(|url| println!("| - {}", url));
+    println!("{}", split_symbol);
+}
+
+// 打印 JSON 数据
+fn print_json_data(json_data: &FlatManifest, split_symbol: &str, output_file: &str) {
+    println!("下面开始更新URL到JSON文件中（文件\"{}\"中的key-value键值对情况，如下）", output_file);
+    println!("{}", split_symbol);
+
+    for (key, values) in json_data {
+        println!("{}:", key);
+        if values.is_empty() {
+            println!("| - []");
+        }
+        values.iter().for_each(|value| println!("| - {}", value));
+    }
+
+    println!("{}", split_symbol);
+}
+
+// 从标准输入读取 key 键名（直到输入非空内容）
+fn prompt_key() -> Result<String> {
+    print!("请您输入要写入JSON文件的key键名：");
+    io::stdout().flush()?;
+
+    let mut update_key = String::new();
+    while update_key.trim().is_empty() {
+        update_key.clear();
+        if io::stdin().read_line(&mut update_key)? == 0 {
+            break;
+        }
+    }
+    Ok(update_key.trim().to_string())
+}
@@ -0,0 +1,44 @@
+//! 控制台辅助函数。
+
+use std::io::{self, IsTerminal, Write};
+
+/// 打印提示并等待用户按下 Enter 键（双击运行时防止窗口一闪而过）。
+pub fn wait_for_enter() {
+    print!("按下Enter键关闭窗口！");
+    io::stdout().flush().expect("刷新输出缓冲区失败");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).expect("读取输入失败");
+}
+
+/// 是否可以从标准输入读取用户输入：非批处理模式，并且标准输入是终端。
+pub fn is_interactive(batch: bool) -> bool {
+    !batch && io::stdin().is_terminal()
+}
+
+/// 只有在交互模式下、并且是双击启动（控制台窗口为本程序独占）时，才等待用户按下 Enter 键。
+pub fn pause_if_needed(batch: bool) {
+    if is_interactive(batch) && launched_by_double_click() {
+        wait_for_enter();
+    }
+}
+
+/// 命令行参数中是否带有 `--batch`（供 app1 ~ app5 使用）。
+pub fn batch_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--batch")
+}
+
+// 控制台窗口中只有本进程时，说明是双击启动的（从 cmd/PowerShell 启动时还有 shell 进程）
+#[cfg(windows)]
+fn launched_by_double_click() -> bool {
+    extern "system" {
+        fn GetConsoleProcessList(process_list: *mut u32, process_count: u32) -> u32;
+    }
+    let mut process_list = [0u32; 2];
+    // SAFETY：缓冲区长度与传入的数量一致
+    unsafe { GetConsoleProcessList(process_list.as_mut_ptr(), process_list.len() as u32) == 1 }
+}
+
+#[cfg(not(windows))]
+fn launched_by_double_click() -> bool {
+    false
+}
@@ -0,0 +1,134 @@
+//! 语义去重：把内容解析后规范化（键排序、数字统一、去掉忽略的路径），按规范化后的值比较，
+//! 只是键的顺序、空白、注释或末尾换行不同的内容视为相同。
+
+use serde::Deserialize;
+use serde_json::{Number, Value};
+
+use crate::hash::content_hash;
+
+/// 去重的选项（清单中的 `dedup`）。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DedupOptions {
+    /// 比较时忽略的路径，用 `.` 分隔，`*` 匹配任意键或下标，如 `log`、`remarks`、`outbounds.*.tag`。
+    pub ignore: Vec<String>,
+}
+
+/// 内容去重用的 key：JSON / YAML 为规范化后的值的哈希；其它格式（或者无法解析）时为
+/// 去掉首尾空白后的文本的哈希。
+pub fn dedup_key(text: &str, format: &str, options: &DedupOptions) -> String {
+    let value = match format.trim().to_lowercase().as_str() {
+        "json" => serde_json::from_str::<Value>(text).ok(),
+        // 键不是字符串的 YAML 无法转换，按文本比较
+        "yaml" | "yml" => serde_yaml::from_str::<Value>(text).ok(),
+        _ => None,
+    };
+    let Some(mut value) = value else {
+        return content_hash(text.trim().as_bytes());
+    };
+    for path in &options.ignore {
+        remove_path(&mut value, &path.split('.').collect::<Vec<_>>());
+    }
+    normalize_numbers(&mut value);
+    // `serde_json::Map` 按键排序，直接输出就是排好序的
+    content_hash(value.to_string().as_bytes())
+}
+
+// 删除 `path` 指向的值
+fn remove_path(value: &mut Value, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    let matches = |key: &str| *first == "*" || *first == key;
+    match value {
+        Value::Object(map) if rest.is_empty() => map.retain(|key, _| !matches(key)),
+        Value::Object(map) => map
+            .iter_mut()
+            .filter(|(key, _)| matches(key))
+            .for_each(|(_, child)| remove_path(child, rest)),
+        Value::Array(list) if rest.is_empty() => {
+            let mut index = 0;
+            list.retain(|_| {
+                index += 1;
+                !matches(&(index - 1).to_string())
+            });
+        }
+        Value::Array(list) => list
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| matches(&index.to_string()))
+            .for_each(|(_, child)| remove_path(child, rest)),
+        _ => {}
+    }
+}
+
+// 值为整数的浮点数（`1.0`、`1e3`）统一为整数
+fn normalize_numbers(value: &mut Value) {
+    match value {
+        Value::Number(number) => {
+            if let Some(float) = number.as_f64().filter(|_| number.is_f64()) {
+                if float.fract() == 0.0 && float.abs() < i64::MAX as f64 {
+                    *number = Number::from(float as i64);
+                }
+            }
+        }
+        Value::Array(list) => list.iter_mut().for_each(normalize_numbers),
+        Value::Object(map) => map.values_mut().for_each(normalize_numbers),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn key(text: &str, format: &str, ignore: &[&str]) -> String {
+        let options = DedupOptions { ignore: ignore.iter().map(|path| path.to_string()).collect() };
+        dedup_key(text, format, &options)
+    }
+
+    #[test]
+    fn ignores_key_order_whitespace_and_comments() {
+        let json = key("{\"a\": 1, \"b\": [1, 2]}", "json", &[]);
+        assert_eq!(json, key("{\n  \"b\": [1,2],\n  \"a\": 1\n}\n", "json", &[]));
+        assert_eq!(json, key("# 注释\nb: [1, 2]\na: 1\n", "yaml", &[]));
+        assert_ne!(json, key("{\"a\": 1, \"b\": [2, 1]}", "json", &[]));
+    }
+
+    #[test]
+    fn integral_floats_equal_integers() {
+        assert_eq!(key("{\"port\": 1.0}", "json", &[]), key("{\"port\": 1}", "json", &[]));
+        assert_eq!(key("{\"port\": 1e3}", "json", &[]), key("{\"port\": 1000}", "json", &[]));
+        assert_ne!(key("{\"port\": 1.5}", "json", &[]), key("{\"port\": 1}", "json", &[]));
+    }
+
+    #[test]
+    fn unparsable_text_compares_trimmed() {
+        assert_eq!(key("vmess://abc\n", "txt", &[]), key("  vmess://abc", "txt", &[]));
+        assert_eq!(key("{broken", "json", &[]), key("{broken\n", "json", &[]));
+    }
+
+    #[test]
+    fn ignores_paths() {
+        let a = "{\"log\": {\"level\": \"info\"}, \"outbounds\": [{\"tag\": \"a\", \"port\": 1}]}";
+        let b = "{\"log\": {\"level\": \"debug\"}, \"outbounds\": [{\"tag\": \"b\", \"port\": 1}]}";
+        assert_ne!(key(a, "json", &["log"]), key(b, "json", &["log"]));
+        assert_eq!(key(a, "json", &["log", "outbounds.*.tag"]), key(b, "json", &["log", "outbounds.*.tag"]));
+    }
+
+    #[test]
+    fn removes_object_keys_and_array_items() {
+        let mut value = json!({"a": {"b": 1, "c": 2}, "list": [{"x": 1}, {"x": 2}, {"x": 3}]});
+        remove_path(&mut value, &["a", "b"]);
+        remove_path(&mut value, &["list", "1"]);
+        remove_path(&mut value, &["list", "*", "y"]);
+        remove_path(&mut value, &["missing", "*"]);
+        assert_eq!(value, json!({"a": {"c": 2}, "list": [{"x": 1}, {"x": 3}]}));
+
+        remove_path(&mut value, &["list", "*"]);
+        remove_path(&mut value, &["*", "c"]);
+        assert_eq!(value, json!({"a": {}, "list": []}));
+    }
+}
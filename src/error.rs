@@ -0,0 +1,100 @@
+//! 库中统一使用的错误类型。
+
+use reqwest::{Response, StatusCode};
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::retry::parse_retry_after;
+
+/// 下载流程中可能出现的错误。
+#[derive(Debug)]
+pub enum Error {
+    /// 清单或输入文件有误（不存在、格式不对、版本不支持等）
+    Manifest(String),
+    /// 网络错误（连接失败、读取响应失败等）
+    Network(reqwest::Error),
+    /// 请求超时
+    Timeout,
+    /// HTTP 状态码不是 200，`retry_after` 为服务器要求的等待时间
+    HttpStatus {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// 响应内容解码失败
+    Decode(String),
+    /// 响应内容不是声明的格式
+    Validate(String),
+    /// 读写本地文件失败
+    Io(io::Error),
+    /// 离线模式下没有这个链接的缓存
+    NotCached,
+    /// 响应内容超过了大小上限（字节）
+    TooLarge { limit: u64 },
+    /// 连接提前结束，收到的内容比 `Content-Length` 少
+    Incomplete { received: u64, expected: u64 },
+}
+
+/// 库中统一使用的 `Result`。
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn manifest(msg: impl fmt::Display) -> Self {
+        Error::Manifest(msg.to_string())
+    }
+
+    /// 根据（状态码不是 200 的）响应生成错误。
+    pub fn from_status(response: &Response) -> Self {
+        Error::HttpStatus {
+            status: response.status(),
+            retry_after: parse_retry_after(response.headers()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Manifest(msg) => write!(f, "清单有误：{}", msg),
+            Error::Network(err) => write!(f, "下载数据失败，检查网络/链接是否有问题，网站是否被墙了。（{}）", err),
+            Error::Timeout => write!(f, "网络资源请求超时！"),
+            Error::HttpStatus { status, .. } => write!(f, "状态码 {}", status),
+            Error::Decode(msg) => write!(f, "内容解码失败：{}", msg),
+            Error::Validate(msg) => write!(f, "内容校验失败：{}", msg),
+            Error::Io(err) => write!(f, "读写文件失败：{}", err),
+            Error::NotCached => write!(f, "离线模式下没有这个链接的缓存"),
+            Error::TooLarge { limit } => write!(f, "内容太大，超过了 {} 字节的上限", limit),
+            Error::Incomplete { received, expected } => {
+                write!(f, "下载不完整：收到 {} 字节，应为 {} 字节", received, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Error::Timeout;
+        }
+        match err.status() {
+            Some(status) => Error::HttpStatus { status, retry_after: None },
+            None => Error::Network(err),
+        }
+    }
+}
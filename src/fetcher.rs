@@ -0,0 +1,380 @@
+//! 下载链接的内容。
+
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::RequestBuilder;
+use std::fs;
+use std::path::{Path, PathBuf};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+use crate::charset::decode_text;
+use crate::client::{Fetched, HttpClient};
+use crate::error::{Error, Result};
+use crate::hash::file_hash;
+use crate::manifest::{Auth, ContentMode, Source};
+use crate::probe::{probe, HostProbes, ProbeMode};
+use crate::report::ItemReport;
+use crate::validate::{validate_binary_file, validate_text, Quarantine};
+use crate::resume::{download_resumable, part_path};
+use crate::writer::{
+    claim_temp_file, create_directory_if_not_exists, find_identical_file, replace_file, store_unique_file, WriteOutcome,
+};
+
+// 文本内容（配置文件）的默认超时时间
+pub(crate) const TEXT_TIMEOUT: Duration = Duration::from_secs(10);
+// 二进制内容一般比较大，默认超时时间长一些
+const BINARY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 来源没有单独指定超时时间时，按处理方式使用的默认超时时间。
+pub(crate) fn default_timeout(mode: Option<ContentMode>) -> Duration {
+    if mode == Some(ContentMode::Binary) {
+        BINARY_TIMEOUT
+    } else {
+        TEXT_TIMEOUT
+    }
+}
+
+/// 按来源的选项下载内容，不解码，边下载边写入文件 `path`，同时返回耗时（不包括等待并发名额的时间）。
+///
+/// 下载失败时删除 `path`。
+pub async fn fetch_source_to_file(
+    client: &HttpClient,
+    source: &Source,
+    default_timeout: Duration,
+    path: &Path,
+) -> (Result<Fetched>, Duration) {
+    let client = &client.for_source(source);
+    let _permit = client.acquire(&source.url).await;
+    let start = Instant::now();
+    let result = async {
+        let request = with_source_options(client, client.get(&source.url), source)?;
+        client.fetch_to_file(request.timeout(source.timeout_or(default_timeout)), path, source.max_size).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(path);
+    }
+    (result, start.elapsed())
+}
+
+/// 按来源指定的字符集、BOM 或 `Content-Type` 解码下载到的内容（见 [`decode_text`]）。
+///
+/// 有无法解码的字节被替换时打印警告。
+pub fn decode_source(source: &Source, bytes: &[u8], content_type: Option<&str>) -> Result<String> {
+    let decoded = decode_text(bytes, content_type, source.charset.as_deref())?;
+    if decoded.lossy {
+        eprintln!("  - 警告：{} 的内容有无法按 {} 解码的字节，已替换为 U+FFFD", source.url, decoded.charset);
+    }
+    Ok(decoded.text)
+}
+
+/// 下载链接对应的文件，保存到 `save_folder` 中，返回保存的文件名和写入的结果。
+///
+/// 文件名取自链接的最后一段，必要时添加编号。内容不解码，边下载边写入 `.part` 文件，
+/// 支持断点续传（见 [`download_resumable`]），按扩展名校验通过后再重命名；
+/// 之前已经保存过内容相同的文件时不再保存，返回已有的文件名。
+/// 没有通过校验的内容隔离到 `save_folder/.rejected/` 中。
+pub async fn download_url(client: &HttpClient, url: &str, save_folder: &str) -> Result<(String, WriteOutcome)> {
+    create_directory_if_not_exists(save_folder)?;
+    let _permit = client.acquire(url).await;
+    let quarantine = Quarantine::new(save_folder, url_file_name(url));
+
+    let part = part_path(url, save_folder);
+    // 离线模式下直接从缓存复制（复制到临时文件，不使用断点续传）
+    let temp_file = if client.is_offline() { part.with_extension("tmp") } else { part };
+    let checked = if client.is_offline() {
+        match client.fetch_to_file(client.get(url), &temp_file, None).await {
+            Ok(fetched) => check_file(url, &temp_file, fetched.content_type.as_deref(), quarantine),
+            Err(err) => Err(err),
+        }
+    } else {
+        let fetched = download_resumable(client, url, &temp_file, None).await?;
+        check_file(url, &temp_file, fetched.content_type.as_deref(), quarantine)
+    };
+    let stored = checked.and_then(|_| store_download(url, &temp_file, save_folder));
+    if stored.is_err() && client.is_offline() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    stored
+}
+
+// 把下载好的临时文件保存到 `save_folder`：已经有内容相同的文件时删除临时文件
+fn store_download(url: &str, temp_file: &Path, save_folder: &str) -> Result<(String, WriteOutcome)> {
+    if let Some(existing) = find_identical_file(url, save_folder, &file_hash(temp_file)?)? {
+        fs::remove_file(temp_file)?;
+        return Ok((existing, WriteOutcome::Unchanged));
+    }
+    let file_name = store_unique_file(url, save_folder, temp_file)?;
+    Ok((file_name, WriteOutcome::Created))
+}
+
+// 链接的最后一段（去掉查询参数）
+pub(crate) fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or("unknown")
+}
+
+// 按链接的扩展名校验下载好的文件：已知的文本格式解码后校验，其它只检查不是 HTML 页面；
+// 没有通过校验时隔离文件
+fn check_file(url: &str, path: &Path, content_type: Option<&str>, quarantine: Quarantine<'_>) -> Result<()> {
+    let format = url_file_name(url).rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default();
+    let result = match ContentMode::for_format(format) {
+        Some(ContentMode::Text) => {
+            let bytes = fs::read(path)?;
+            validate_text(&decode_text(&bytes, content_type, None)?.text, format)
+        }
+        _ => validate_binary_file(path),
+    };
+    if let Err(err @ Error::Validate(_)) = &result {
+        quarantine.file(url, path, err);
+    }
+    result
+}
+
+/// 从一组镜像中选出一个下载的方式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BestStrategy {
+    /// 按优先级顺序逐个尝试，前一个失败了才尝试下一个。
+    Sequential,
+    /// 竞速：按优先级每隔 `stagger` 启动一个镜像（前一个失败时立即启动下一个），
+    /// 采用第一个通过校验的响应，取消其余的请求。
+    Race { stagger: Duration },
+}
+
+impl Default for BestStrategy {
+    fn default() -> Self {
+        BestStrategy::Race { stagger: Duration::from_millis(250) }
+    }
+}
+
+/// [`download_best`] 的选项。
+#[derive(Debug, Clone, Copy)]
+pub struct BestOptions<'a> {
+    pub strategy: BestStrategy,
+    /// 下载前确认镜像可用的方式。
+    pub probe: ProbeMode,
+    /// 每个站点支持的预检方式（自动预检时读取并更新）。
+    pub probes: &'a HostProbes,
+}
+
+/// 从一组镜像中找出可用的一个下载，保存为 `save_folder/task_name.data_file`。
+///
+/// 按 `options` 中的方式预检、尝试各个镜像（内容没有通过校验的镜像视为失败，
+/// 内容隔离到 `save_folder/.rejected/` 中），返回下载成功的链接；所有链接都失败时返回 `None`。
+/// 内容边下载边写入临时文件，校验通过后移动到位，内容没有变化时不替换（见 [`replace_file`]）。
+/// 每个完成的链接（及其耗时）和输出文件都记录到 `report` 中，写入文件失败时整个任务记为失败。
+pub async fn download_best(
+    client: &HttpClient,
+    task_name: &str,
+    sources: &[&Source],
+    data_file: &str,
+    save_folder: &str,
+    options: &BestOptions<'_>,
+    report: &mut ItemReport,
+) -> Option<String> {
+    // 检查文件夹是否存在，不存在就创建
+    if let Err(err) = create_directory_if_not_exists(save_folder) {
+        report.fail(err);
+        return None;
+    }
+
+    // 每个镜像下载到自己的临时文件，胜出的移动到位，其余的（包括竞速中被取消的）最后删除
+    let temp_name = format!("{}.{}", task_name, data_file);
+    let mut temp_files = Vec::new();
+    for _ in sources {
+        match claim_temp_file(Path::new(save_folder), &temp_name) {
+            Ok(temp_file) => temp_files.push(temp_file),
+            Err(err) => {
+                remove_files(&temp_files);
+                report.fail(err);
+                return None;
+            }
+        }
+    }
+    let mirrors: Vec<(&Source, &Path)> = sources.iter().copied().zip(temp_files.iter().map(PathBuf::as_path)).collect();
+
+    let quarantine = Quarantine::new(save_folder, task_name);
+    let winner = match options.strategy {
+        BestStrategy::Sequential => sequential(client, &mirrors, data_file, options, quarantine, report).await,
+        BestStrategy::Race { stagger } => race(client, &mirrors, data_file, stagger, options, quarantine, report).await,
+    };
+    let url = winner.and_then(|(source, temp_file, elapsed)| {
+        let file_name = format!("{}/{}.{}", save_folder, task_name, source.format_or(data_file));
+        let stored = file_hash(temp_file)
+            .map_err(Error::from)
+            .and_then(|hash| Ok((hash, replace_file(&file_name, temp_file)?)));
+        match stored {
+            Ok((hash, outcome)) => {
+                report.record_written(&file_name, outcome);
+                report.record_fetched(&source.url, elapsed, hash);
+                Some(source.url.clone())
+            }
+            Err(err) => {
+                report.fail(err);
+                None
+            }
+        }
+    });
+    remove_files(&temp_files);
+    url
+}
+
+// 删除（剩下的）临时文件
+fn remove_files(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// 逐个尝试，返回第一个成功的镜像、保存内容的临时文件和耗时
+async fn sequential<'a, 't>(
+    client: &HttpClient,
+    mirrors: &[(&'a Source, &'t Path)],
+    data_file: &str,
+    options: &BestOptions<'_>,
+    quarantine: Quarantine<'_>,
+    report: &mut ItemReport,
+) -> Option<(&'a Source, &'t Path, Duration)> {
+    for &(source, temp_file) in mirrors {
+        let (result, elapsed) = fetch_mirror(client, source, temp_file, data_file, options, quarantine).await;
+        match result {
+            Ok(()) => return Some((source, temp_file, elapsed)),
+            Err(e) => {
+                println!("GET {} 失败: {}（{:?}），跳过", source.url, e, elapsed);
+                report.record_timed(&source.url, Some(e), elapsed);
+            }
+        }
+    }
+    None
+}
+
+// 竞速，返回最先成功的镜像、保存内容的临时文件和耗时；其余还在进行的请求随 `running` 一起取消
+async fn race<'a, 't>(
+    client: &HttpClient,
+    mirrors: &[(&'a Source, &'t Path)],
+    data_file: &str,
+    stagger: Duration,
+    options: &BestOptions<'_>,
+    quarantine: Quarantine<'_>,
+    report: &mut ItemReport,
+) -> Option<(&'a Source, &'t Path, Duration)> {
+    let mut waiting = mirrors.iter().copied();
+    let mut running = FuturesUnordered::new();
+    let mut launched: Vec<(&Source, Instant)> = Vec::new();
+    let mut next_launch = Instant::now();
+
+    let mut launch = |(source, temp_file): (&'a Source, &'t Path), running: &mut FuturesUnordered<_>| {
+        launched.push((source, Instant::now()));
+        running.push(async move {
+            let result = fetch_mirror(client, source, temp_file, data_file, options, quarantine).await;
+            (source, temp_file, result)
+        });
+    };
+
+    loop {
+        if running.is_empty() {
+            launch(waiting.next()?, &mut running);
+            next_launch = Instant::now() + stagger;
+        }
+        tokio::select! {
+            Some((source, temp_file, (result, elapsed))) = running.next() => match result {
+                Ok(()) => {
+                    println!("{} 胜出（{:?}）", source.url, elapsed);
+                    drop(running);
+                    for (other, started) in launched.iter().filter(|(other, _)| other.url != source.url) {
+                        if !report.attempts.iter().any(|attempt| attempt.url == other.url) {
+                            println!("  - {} 已取消（{:?}）", other.url, started.elapsed());
+                        }
+                    }
+                    return Some((source, temp_file, elapsed));
+                }
+                Err(e) => {
+                    println!("GET {} 失败: {}（{:?}），跳过", source.url, e, elapsed);
+                    report.record_timed(&source.url, Some(e), elapsed);
+                    // 失败后立即启动下一个镜像，不必等到下一个启动时间
+                    if let Some(mirror) = waiting.next() {
+                        launch(mirror, &mut running);
+                        next_launch = Instant::now() + stagger;
+                    }
+                }
+            },
+            _ = sleep_until(next_launch), if waiting.len() > 0 => {
+                launch(waiting.next()?, &mut running);
+                next_launch = Instant::now() + stagger;
+            }
+        }
+    }
+}
+
+// 尝试一个镜像：先预检确认可用，再 GET 下载内容（可能来自缓存），边下载边写入 `temp_file` 后校验，
+// 返回结果和耗时；预检和下载都有超时时间（来源的 `timeout`，没有指定时按处理方式取默认值）
+async fn fetch_mirror(
+    client: &HttpClient,
+    source: &Source,
+    temp_file: &Path,
+    data_file: &str,
+    options: &BestOptions<'_>,
+    quarantine: Quarantine<'_>,
+) -> (Result<()>, Duration) {
+    let client = &client.for_source(source);
+    let _permit = client.acquire(&source.url).await;
+    let start = Instant::now();
+    let result = async {
+        // 离线模式下不发送请求，直接使用缓存
+        if !client.is_offline() {
+            probe(client, source, options.probe, options.probes).await?;
+        }
+        let timeout = source.timeout_or(default_timeout(source.mode_or(data_file)));
+        let request = with_source_options(client, client.get(&source.url), source)?.timeout(timeout);
+        let fetched = client.fetch_to_file(request, temp_file, source.max_size).await?;
+        if let Err(err) = check_downloaded(source, temp_file, fetched.content_type.as_deref(), data_file) {
+            if matches!(err, Error::Validate(_)) {
+                quarantine.file(&source.url, temp_file, &err);
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+    .await;
+    (result, start.elapsed())
+}
+
+// 按来源的处理方式校验下载好的文件：文本按来源的字符集解码后按格式校验，二进制只检查不是 HTML 页面
+fn check_downloaded(source: &Source, path: &Path, content_type: Option<&str>, data_file: &str) -> Result<()> {
+    match source.mode_or(data_file).unwrap_or_else(|| ContentMode::for_content_type(content_type)) {
+        ContentMode::Binary => validate_binary_file(path),
+        ContentMode::Text => {
+            let bytes = fs::read(path)?;
+            validate_text(&decode_source(source, &bytes, content_type)?, source.format_or(data_file))
+        }
+    }
+}
+
+// 加上来源自己的 User-Agent、请求头和认证信息，其中引用的密钥在这里取出
+pub(crate) fn with_source_options(client: &HttpClient, request: RequestBuilder, source: &Source) -> Result<RequestBuilder> {
+    let secrets = client.secrets();
+    let mut headers = HeaderMap::new();
+    if let Some(user_agent) = &source.user_agent {
+        headers.insert(header::USER_AGENT, header_value(user_agent, false)?);
+    }
+    for (name, value) in &source.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::manifest(format!("{} 的请求头名称无效：{}", source.url, name)))?;
+        // 引用了密钥的请求头标记为敏感，不会出现在调试输出中
+        let sensitive = value.contains("${");
+        headers.insert(name, header_value(&secrets.expand(value)?, sensitive)?);
+    }
+    let request = request.headers(headers);
+    Ok(match &source.auth {
+        None => request,
+        Some(Auth::Bearer(token)) => request.bearer_auth(secrets.resolve(token)?),
+        Some(Auth::Basic { username, password }) => request.basic_auth(username, Some(secrets.resolve(password)?)),
+    })
+}
+
+fn header_value(value: &str, sensitive: bool) -> Result<HeaderValue> {
+    let mut value = HeaderValue::from_str(value).map_err(|_| Error::manifest("请求头的值中有不允许的字符（如换行）"))?;
+    value.set_sensitive(sensitive);
+    Ok(value)
+}
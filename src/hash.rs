@@ -0,0 +1,30 @@
+//! 内容哈希（SHA-256），用于去重、缓存文件名和镜像健康度。
+
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// 内容的哈希（SHA-256，十六进制）。
+pub fn content_hash(content: &[u8]) -> String {
+    to_hex(&Sha256::digest(content))
+}
+
+/// 文件内容的哈希，分块读取，不会把整个文件读入内存。
+pub fn file_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
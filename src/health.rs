@@ -0,0 +1,255 @@
+//! 镜像健康度：在本地状态文件中记录每个镜像的历史表现，
+//! 用来给镜像排序、跳过长期失效的镜像。
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::manifest::Source;
+use crate::probe::{HostProbes, ProbeMode};
+use crate::report::RunReport;
+use crate::writer::write_atomic;
+
+/// 默认的状态文件。
+pub const DEFAULT_HEALTH_FILE: &str = "mirror-health.json";
+
+// 计算中位延迟时保留最近多少次成功的耗时
+const LATENCY_WINDOW: usize = 20;
+// 连续失败多少次算作长期失效
+const DEAD_AFTER_FAILURES: u32 = 5;
+// 长期失效的镜像，距最近一次失败超过这个时间（秒）后再试一次
+const DEAD_RETRY_AFTER: u64 = 24 * 60 * 60;
+
+/// 一个镜像（链接）的历史表现。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorHealth {
+    pub successes: u32,
+    pub failures: u32,
+    /// 连续失败的次数，成功一次后清零。
+    pub consecutive_failures: u32,
+    /// 最近几次成功的耗时（毫秒）。
+    pub latencies_ms: VecDeque<u64>,
+    pub last_failure: Option<Failure>,
+    /// 最近一次下载到的内容的哈希。
+    pub content_hash: Option<String>,
+    /// 内容最近一次变化的时间（Unix 时间戳，秒）。
+    pub content_changed_at: Option<u64>,
+}
+
+/// 最近一次失败。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    /// 失败的时间（Unix 时间戳，秒）。
+    pub at: u64,
+    pub error: String,
+}
+
+impl MirrorHealth {
+    /// 成功率，没有记录时为 `None`。
+    pub fn success_rate(&self) -> Option<f64> {
+        let total = self.successes + self.failures;
+        (total > 0).then(|| f64::from(self.successes) / f64::from(total))
+    }
+
+    /// 最近几次成功的耗时的中位数。
+    pub fn median_latency(&self) -> Option<Duration> {
+        let mut latencies: Vec<u64> = self.latencies_ms.iter().copied().collect();
+        latencies.sort_unstable();
+        latencies.get(latencies.len() / 2).map(|ms| Duration::from_millis(*ms))
+    }
+
+    /// 是否长期失效：连续失败多次，并且最近一次失败还不久。
+    pub fn is_dead(&self, now: u64) -> bool {
+        self.consecutive_failures >= DEAD_AFTER_FAILURES
+            && self.last_failure.as_ref().is_some_and(|failure| now < failure.at + DEAD_RETRY_AFTER)
+    }
+
+    // 排序用的得分：平滑后的成功率，没有记录的镜像为 0.5
+    fn score(&self) -> f64 {
+        f64::from(self.successes + 1) / f64::from(self.successes + self.failures + 2)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateFile {
+    #[serde(default)]
+    mirrors: BTreeMap<String, MirrorHealth>,
+    /// 每个站点支持的预检方式。
+    #[serde(default)]
+    probes: BTreeMap<String, ProbeMode>,
+}
+
+/// 所有镜像的健康度，以及对应的状态文件。
+#[derive(Debug, Default)]
+pub struct HealthStore {
+    path: Option<PathBuf>,
+    mirrors: BTreeMap<String, MirrorHealth>,
+    probes: HostProbes,
+    changed: bool,
+}
+
+impl HealthStore {
+    /// 读取状态文件；文件不存在时从空白开始，无法读取或解析时打印警告后从空白开始。
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let state = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str::<StateFile>(&text).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(StateFile::default()),
+            Err(err) => Err(err.to_string()),
+        };
+        let state = state.unwrap_or_else(|err| {
+            eprintln!("镜像健康度文件 {} 无法读取，重新开始统计（{}）", path.display(), err);
+            StateFile::default()
+        });
+        Self {
+            path: Some(path.to_path_buf()),
+            mirrors: state.mirrors,
+            probes: HostProbes::new(state.probes),
+            changed: false,
+        }
+    }
+
+    /// 有新记录时（原子地）写回状态文件。
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| self.changed || self.probes.is_changed()) else {
+            return Ok(());
+        };
+        let state = StateFile { mirrors: self.mirrors.clone(), probes: self.probes.snapshot() };
+        let text = serde_json::to_string_pretty(&state).map_err(|err| Error::Io(err.into()))?;
+        write_atomic(path, text.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn get(&self, url: &str) -> Option<&MirrorHealth> {
+        self.mirrors.get(url)
+    }
+
+    /// 每个站点支持的预检方式。
+    pub fn probes(&self) -> &HostProbes {
+        &self.probes
+    }
+
+    /// 记录一次成功，`hash` 为下载到的内容的哈希（有时）。
+    pub fn record_success(&mut self, url: &str, elapsed: Option<Duration>, hash: Option<&str>) {
+        let mirror = self.mirrors.entry(url.to_string()).or_default();
+        mirror.successes += 1;
+        mirror.consecutive_failures = 0;
+        if let Some(elapsed) = elapsed {
+            if mirror.latencies_ms.len() == LATENCY_WINDOW {
+                mirror.latencies_ms.pop_front();
+            }
+            mirror.latencies_ms.push_back(elapsed.as_millis() as u64);
+        }
+        if let Some(hash) = hash {
+            if mirror.content_hash.as_deref() != Some(hash) {
+                mirror.content_hash = Some(hash.to_string());
+                mirror.content_changed_at = Some(unix_now());
+            }
+        }
+        self.changed = true;
+    }
+
+    /// 记录一次失败。
+    pub fn record_failure(&mut self, url: &str, error: &Error) {
+        let mirror = self.mirrors.entry(url.to_string()).or_default();
+        mirror.failures += 1;
+        mirror.consecutive_failures += 1;
+        mirror.last_failure = Some(Failure { at: unix_now(), error: error.to_string() });
+        self.changed = true;
+    }
+
+    /// 记录运行报告中所有链接的结果。
+    pub fn record_report(&mut self, report: &RunReport) {
+        for attempt in report.items.iter().flat_map(|item| &item.attempts) {
+            match &attempt.error {
+                None => self.record_success(&attempt.url, attempt.elapsed, attempt.content_hash.as_deref()),
+                Some(err) => self.record_failure(&attempt.url, err),
+            }
+        }
+    }
+
+    /// 去掉长期失效的镜像，其余的保持原来的顺序。所有镜像都长期失效时不去掉任何一个。
+    pub fn skip_dead<'a>(&self, sources: Vec<&'a Source>) -> Vec<&'a Source> {
+        let now = unix_now();
+        let (dead, alive): (Vec<&Source>, Vec<&Source>) =
+            sources.iter().partition(|source| self.get(&source.url).is_some_and(|health| health.is_dead(now)));
+        if alive.is_empty() {
+            return sources;
+        }
+        for source in dead {
+            let failures = self.mirrors[&source.url].consecutive_failures;
+            println!("  - 跳过长期失效的镜像 {}（连续失败 {} 次）", source.url, failures);
+        }
+        alive
+    }
+
+    /// 给镜像排序：先按清单中的优先级，再按成功率、中位延迟；并去掉长期失效的镜像
+    /// （见 [`skip_dead`](Self::skip_dead)）。
+    pub fn order<'a>(&self, sources: Vec<&'a Source>) -> Vec<&'a Source> {
+        let unknown = MirrorHealth::default();
+        let health = |source: &Source| self.mirrors.get(&source.url).unwrap_or(&unknown);
+
+        let mut alive = self.skip_dead(sources);
+        alive.sort_by(|a, b| {
+            let (ha, hb) = (health(a), health(b));
+            b.priority
+                .cmp(&a.priority)
+                .then(hb.score().total_cmp(&ha.score()))
+                .then(ha.median_latency().unwrap_or(Duration::MAX).cmp(&hb.median_latency().unwrap_or(Duration::MAX)))
+        });
+        alive
+    }
+
+    /// 打印镜像健康度表。
+    pub fn print_table(&self) {
+        if self.mirrors.is_empty() {
+            println!("还没有任何镜像的记录。");
+            return;
+        }
+        let now = unix_now();
+        println!("{:>8} {:>10} {:>8} {:>12} {:>12}  镜像", "成功率", "中位延迟", "连续失败", "最近失败", "内容哈希");
+        for (url, mirror) in &self.mirrors {
+            let rate = mirror.success_rate().map(|rate| format!("{:.0}%", rate * 100.0)).unwrap_or_default();
+            let latency = mirror.median_latency().map(|d| format!("{}ms", d.as_millis())).unwrap_or("-".to_string());
+            let last_failure = match &mirror.last_failure {
+                Some(failure) => format_ago(now.saturating_sub(failure.at)),
+                None => "-".to_string(),
+            };
+            let hash = mirror.content_hash.as_deref().map(|hash| &hash[..12.min(hash.len())]).unwrap_or("-");
+            let dead = if mirror.is_dead(now) { "（已跳过）" } else { "" };
+            println!(
+                "{:>11} {:>14} {:>12} {:>16} {:>16}  {}{}",
+                rate, latency, mirror.consecutive_failures, last_failure, hash, url, dead
+            );
+        }
+        let range_hosts: Vec<String> = self
+            .probes
+            .snapshot()
+            .into_iter()
+            .filter(|(_, mode)| *mode == ProbeMode::Range)
+            .map(|(host, _)| host)
+            .collect();
+        if !range_hosts.is_empty() {
+            println!("\n不支持 HEAD、改用范围 GET 预检的站点：{}", range_hosts.join("、"));
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// 把秒数显示为“N 分钟前”之类
+fn format_ago(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{} 秒前", seconds),
+        60..=3599 => format!("{} 分钟前", seconds / 60),
+        3600..=86399 => format!("{} 小时前", seconds / 3600),
+        _ => format!("{} 天前", seconds / 86400),
+    }
+}
@@ -0,0 +1,51 @@
+//! 下载配置文件的公共库，app1 ~ app5 只是它的前端。
+//!
+//! 整个下载流程拆成下面几个模块，其它工具可以直接嵌入使用：
+//!
+//! - [`manifest`]：读取 `url.txt`、`flat-json.json`、`urls.json`、`urls.yaml` 等清单文件
+//! - [`client`]：共享的 HTTP 客户端（连接池）
+//! - [`proxy`]：上游代理（HTTP / SOCKS5，直连失败后回退到代理）
+//! - [`secrets`]：密钥（环境变量、密钥文件），供认证和请求头引用
+//! - [`cache`]：磁盘上的 HTTP 缓存（ETag / If-Modified-Since）
+//! - [`retry`]：重试策略（指数退避 + 抖动）
+//! - [`limiter`]：并发限制（全局 + 每个站点）
+//! - [`fetcher`]：下载链接的内容
+//! - [`probe`]：选出最优链接前的预检（HEAD / 范围 GET），记录各站点支持的方式
+//! - [`hash`]：内容哈希（SHA-256）
+//! - [`health`]：镜像健康度（本地状态文件），用来给镜像排序
+//! - [`charset`]：字符集检测与解码
+//! - [`resume`]：断点续传（`.part` 文件 + `Range` 请求）
+//! - [`validate`]：校验下载到的内容，隔离被拒绝的内容
+//! - [`normalizer`]：格式化（规范化）下载到的内容
+//! - [`dedup`]：语义去重（规范化后比较，可以忽略部分路径）
+//! - [`pipeline`]：下载 + 规范化 + 去重
+//! - [`numbering`]：输出文件的编号（内容不变时编号不变）
+//! - [`writer`]：将数据写入输出文件夹（原子写入，跳过内容没有变化的文件）
+//! - [`report`]：运行报告与退出码
+//! - [`error`]：统一的错误类型
+//! - [`commands`]：`dlconf` 各子命令（app1 ~ app5 的流程）
+//! - [`console`]：控制台辅助函数
+
+pub mod cache;
+pub mod charset;
+pub mod client;
+pub mod commands;
+pub mod console;
+pub mod dedup;
+pub mod error;
+pub mod fetcher;
+pub mod hash;
+pub mod health;
+pub mod limiter;
+pub mod manifest;
+pub mod normalizer;
+pub mod numbering;
+pub mod pipeline;
+pub mod probe;
+pub mod proxy;
+pub mod report;
+pub mod resume;
+pub mod retry;
+pub mod secrets;
+pub mod validate;
+pub mod writer;
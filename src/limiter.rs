@@ -0,0 +1,63 @@
+//! 并发限制：全局最多同时进行的下载数，以及每个站点最多同时进行的下载数。
+
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 并发限制器，由 [`HttpClient`](crate::client::HttpClient) 在所有下载之间共享。
+#[derive(Debug)]
+pub struct Limiter {
+    global: Arc<Semaphore>,
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// 一次下载占用的名额，释放（drop）后其它下载才能开始。
+#[derive(Debug)]
+pub struct Permit {
+    _host: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+impl Limiter {
+    /// `max_concurrency` 为全局上限，`max_per_host` 为每个站点的上限，0 表示不限制。
+    pub fn new(max_concurrency: usize, max_per_host: usize) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(limit_or_max(max_concurrency))),
+            per_host: limit_or_max(max_per_host),
+            hosts: Mutex::default(),
+        }
+    }
+
+    /// 等待 `url` 所在站点和全局都有空余名额。
+    pub async fn acquire(&self, url: &str) -> Permit {
+        // 先占站点的名额，再占全局的名额，避免等待某个繁忙站点时占着全局名额
+        let host = self.host_semaphore(url);
+        let host = host.acquire_owned().await.expect("信号量不会被关闭");
+        let global = self.global.clone().acquire_owned().await.expect("信号量不会被关闭");
+        Permit { _host: host, _global: global }
+    }
+
+    fn host_semaphore(&self, url: &str) -> Arc<Semaphore> {
+        let host = host_key(url);
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.entry(host).or_insert_with(|| Arc::new(Semaphore::new(self.per_host))).clone()
+    }
+}
+
+/// 链接所在的站点（`主机:端口`），无法解析的链接为空字符串（共用一个名额池，请求本身会报错）。
+pub(crate) fn host_key(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| Some(format!("{}:{}", url.host_str()?, url.port_or_known_default()?)))
+        .unwrap_or_default()
+}
+
+fn limit_or_max(limit: usize) -> usize {
+    if limit == 0 {
+        Semaphore::MAX_PERMITS
+    } else {
+        limit
+    }
+}
@@ -0,0 +1,700 @@
+//! 清单文件的读取与更新。
+//!
+//! - `url.txt`：每行一个链接
+//! - `flat-json.json`：一层结构 `key → urls`
+//! - `urls.json` / `urls.yaml`：两层结构 `格式 → key → urls`
+//!
+//! [`load_manifest`] 可以读取 JSON、YAML、TOML 格式的清单（根据扩展名或内容判断），
+//! 一层结构和两层结构都能识别，一层结构会按 key 名推断格式后转换为两层结构。
+//!
+//! 清单中的每个链接，既可以是字符串，也可以是带选项的对象：
+//!
+//! ```yaml
+//! version: 2
+//! json:
+//!   xray:
+//!     - https://gitlab.com/free9999/ipupdate/-/raw/master/xray/config.json
+//!     - url: https://fastly.jsdelivr.net/gh/Alvin9999/pac2@latest/xray/config.json
+//!       timeout: 20
+//!       headers:
+//!         User-Agent: clash.meta
+//!       format: json
+//!       priority: 10
+//!       enabled: true
+//!       charset: utf-8
+//! dat:
+//!   geoip:
+//!     - url: https://github.com/Loyalsoldier/v2ray-rules-dat/releases/latest/download/geoip.dat
+//!       mode: binary
+//!       max_size: 104857600
+//! ```
+//!
+//! 同一个文件在多个镜像上的链接，可以用镜像模板生成：在 `mirrors` 中声明一组链接模板，
+//! 条目中用 `mirrors` 引用模板名称，用 `path`（以及 `vars`）填入模板中的占位符，
+//! 其它选项应用到生成的每个链接上：
+//!
+//! ```yaml
+//! mirrors:
+//!   pac2:
+//!     - https://www.gitlabip.xyz/Alvin9999/pac2/master/{path}
+//!     - https://gitlab.com/free9999/ipupdate/-/raw/master/{path}
+//!     - https://www.githubip.xyz/Alvin9999/pac2/master/{path}
+//!     - https://fastly.jsdelivr.net/gh/Alvin9999/pac2@latest/{path}
+//! json:
+//!   singbox:
+//!     - { mirrors: pac2, path: singbox/config.json }
+//! ```
+//!
+//! `dedup.ignore` 列出去重时忽略的路径（见 [`dedup`](crate::dedup)），例如
+//! `dedup: { ignore: [log, remarks] }`。
+//!
+//! 认证信息中的密钥只能写成 `${env:…}` 或 `${secret:…}` 引用（见 [`secrets`](crate::secrets)），
+//! 请求头的值中也可以使用这种引用。
+//!
+//! 没有 `version` 字段的旧清单视为第 1 版，照常读取。
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use crate::dedup::DedupOptions;
+use crate::error::{Error, Result};
+use crate::proxy::{ProxyMode, ProxyUrl};
+use crate::secrets::SecretRef;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 当前支持的清单版本。
+pub const MANIFEST_VERSION: u32 = 2;
+
+/// 两层结构的清单：`格式(json/yaml) → key → 链接`。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Manifest {
+    /// 清单版本，旧清单没有这个字段，视为第 1 版。
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+    /// 去重的选项。
+    #[serde(default)]
+    pub dedup: DedupOptions,
+    /// `格式 → key → 链接`
+    #[serde(flatten, deserialize_with = "deserialize_sections")]
+    pub sections: Sections,
+}
+
+impl Manifest {
+    fn check_version(&self) -> Result<()> {
+        if self.version > MANIFEST_VERSION {
+            return Err(Error::manifest(format!(
+                "清单版本 {} 过新，当前最高支持第 {} 版",
+                self.version, MANIFEST_VERSION
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+/// 清单中的一个链接及其选项。
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Source {
+    pub url: String,
+    /// 超时时间（秒），不指定时使用默认值。
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// 额外的请求头，值中可以引用密钥。
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// User-Agent（如 `clash.meta`），不指定时使用全局的 User-Agent。
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 认证方式。
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    /// 期望的内容格式（json / yaml），不指定时使用所在分组的格式。
+    #[serde(default)]
+    pub format: Option<String>,
+    /// 优先级，数值越大越优先，默认 0。
+    #[serde(default)]
+    pub priority: i32,
+    /// 是否启用，默认启用。
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// 按文本还是二进制处理内容，不指定时根据格式或 `Content-Type` 判断。
+    #[serde(default)]
+    pub mode: Option<ContentMode>,
+    /// 文本内容的字符集（如 `gbk`），不指定时根据 BOM 或 `Content-Type` 判断。
+    #[serde(default)]
+    pub charset: Option<String>,
+    /// 内容的大小上限（字节），不指定时使用全局的上限。
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// 保留服务器返回的原始内容（原始字节，不转换字符集），不重新格式化。
+    #[serde(default)]
+    pub keep_original: bool,
+    /// 上游代理，不指定时使用全局的代理。
+    #[serde(default)]
+    pub proxy: Option<ProxyUrl>,
+    /// 代理的使用方式（always / fallback / direct），不指定时使用全局的设置。
+    #[serde(default)]
+    pub proxy_mode: Option<ProxyMode>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// 来源的认证方式，密钥用 `${env:…}` 或 `${secret:…}` 引用。
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Auth {
+    /// `Authorization: Bearer <token>`
+    Bearer(SecretRef),
+    /// HTTP Basic 认证。
+    Basic { username: String, password: SecretRef },
+}
+
+/// 内容的处理方式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentMode {
+    /// 文本：解码后规范化、去重。
+    Text,
+    /// 二进制（`.dat`、`.mmdb`、`.srs` 等）：不解码，原样保存到磁盘，按内容哈希去重。
+    Binary,
+}
+
+// 已知的文本格式和二进制格式
+const TEXT_FORMATS: &[&str] = &["json", "yaml", "yml", "toml", "txt", "conf", "list"];
+const BINARY_FORMATS: &[&str] = &["dat", "mmdb", "srs", "mrs", "db", "bin", "gz", "zip"];
+
+impl ContentMode {
+    /// 根据格式（扩展名）判断处理方式，未知的格式返回 `None`。
+    pub fn for_format(format: &str) -> Option<Self> {
+        let format = format.trim().to_lowercase();
+        if TEXT_FORMATS.contains(&format.as_str()) {
+            Some(ContentMode::Text)
+        } else if BINARY_FORMATS.contains(&format.as_str()) {
+            Some(ContentMode::Binary)
+        } else {
+            None
+        }
+    }
+
+    /// 根据 `Content-Type` 判断处理方式，没有 `Content-Type` 时按文本处理。
+    pub fn for_content_type(content_type: Option<&str>) -> Self {
+        let Some(content_type) = content_type else {
+            return ContentMode::Text;
+        };
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        let textual = mime.starts_with("text/")
+            || ["json", "yaml", "toml", "xml", "javascript"].iter().any(|kind| mime.contains(kind));
+        if textual {
+            ContentMode::Text
+        } else {
+            ContentMode::Binary
+        }
+    }
+}
+
+impl Source {
+    /// 只有链接、所有选项都取默认值的来源。
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            timeout: None,
+            headers: BTreeMap::new(),
+            user_agent: None,
+            auth: None,
+            format: None,
+            priority: 0,
+            enabled: true,
+            mode: None,
+            charset: None,
+            max_size: None,
+            keep_original: false,
+            proxy: None,
+            proxy_mode: None,
+        }
+    }
+
+    /// 本来源的超时时间，没有单独指定时为 `default`。
+    pub fn timeout_or(&self, default: Duration) -> Duration {
+        self.timeout.map(Duration::from_secs).unwrap_or(default)
+    }
+
+    /// 本来源的内容格式，没有单独指定时为 `default`。
+    pub fn format_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.format.as_deref().unwrap_or(default)
+    }
+
+    /// 本来源的处理方式：单独指定的，或者根据格式判断的；都没有时为 `None`（看 `Content-Type`）。
+    pub fn mode_or(&self, default_format: &str) -> Option<ContentMode> {
+        self.mode.or_else(|| ContentMode::for_format(self.format_or(default_format)))
+    }
+}
+
+// 链接可以是字符串，也可以是带选项的对象
+impl<'de> Deserialize<'de> for SourceEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = SourceEntry;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("链接字符串，或带 url 字段的对象")
+            }
+
+            fn visit_str<E: de::Error>(self, url: &str) -> std::result::Result<Self::Value, E> {
+                Ok(SourceEntry(Source::new(url)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<Self::Value, A::Error> {
+                Source::deserialize(de::value::MapAccessDeserializer::new(map)).map(SourceEntry)
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+// 反序列化时的中间类型，见上面的 Deserialize 实现
+struct SourceEntry(Source);
+
+type Sections = BTreeMap<String, BTreeMap<String, Vec<Source>>>;
+
+fn deserialize_sections<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Sections, D::Error> {
+    let sections: BTreeMap<String, BTreeMap<String, Vec<SourceEntry>>> = Deserialize::deserialize(deserializer)?;
+    Ok(sections
+        .into_iter()
+        .map(|(format, keys)| {
+            let keys = keys
+                .into_iter()
+                .map(|(key, entries)| (key, entries.into_iter().map(|entry| entry.0).collect()))
+                .collect();
+            (format, keys)
+        })
+        .collect())
+}
+
+/// 启用的来源，按优先级从高到低排序（优先级相同时保持清单中的顺序）。
+pub fn active_sources(sources: &[Source]) -> Vec<&Source> {
+    let mut active: Vec<&Source> = sources.iter().filter(|source| source.enabled).collect();
+    active.sort_by_key(|source| std::cmp::Reverse(source.priority));
+    active
+}
+
+/// 一层结构的清单：`key → urls`。
+pub type FlatManifest = BTreeMap<String, Vec<String>>;
+
+/// 清单文件的格式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ManifestFormat {
+    /// 根据文件扩展名推断格式。
+    pub fn from_path(file_path: &str) -> Option<Self> {
+        let ext = Path::new(file_path).extension()?.to_str()?;
+        ext.parse().ok()
+    }
+
+    /// 根据内容推断格式：依次尝试 JSON、TOML，都不是时当作 YAML。
+    pub fn sniff(content: &str) -> Self {
+        if serde_json::from_str::<Value>(content).is_ok() {
+            Self::Json
+        } else if content.parse::<toml::Table>().is_ok() {
+            Self::Toml
+        } else {
+            Self::Yaml
+        }
+    }
+
+    // 解析为通用的 JSON 值，方便统一处理
+    pub(crate) fn parse(self, content: &str) -> Result<Value> {
+        match self {
+            Self::Json => serde_json::from_str(content).map_err(Error::manifest),
+            Self::Yaml => serde_yaml::from_str(content).map_err(Error::manifest),
+            Self::Toml => toml::from_str(content).map_err(Error::manifest),
+        }
+    }
+}
+
+impl FromStr for ManifestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "toml" => Ok(Self::Toml),
+            other => Err(format!("不支持的清单格式：{}", other)),
+        }
+    }
+}
+
+/// 读取任意格式、任意结构的清单文件。
+///
+/// 未指定格式时，先根据扩展名判断，判断不了再根据内容判断。
+pub fn load_manifest(file_path: &str, format: Option<ManifestFormat>) -> Result<Manifest> {
+    let content = read_input(file_path)?;
+    let format = format.or_else(|| ManifestFormat::from_path(file_path));
+    parse_manifest(&content, format).map_err(|err| in_file(err, file_path))
+}
+
+/// 解析清单内容，`format` 为 `None` 时根据内容判断格式。
+pub fn parse_manifest(content: &str, format: Option<ManifestFormat>) -> Result<Manifest> {
+    let format = format.unwrap_or_else(|| ManifestFormat::sniff(content));
+    let value = format.parse(content)?;
+    let mut value = into_two_level(value)?;
+    expand_mirrors(&mut value)?;
+    let manifest: Manifest = serde_json::from_value(value).map_err(Error::manifest)?;
+    manifest.check_version()?;
+    Ok(manifest)
+}
+
+// 一层结构（key → urls）转换为两层结构（格式 → key → urls），两层结构原样返回
+fn into_two_level(value: Value) -> Result<Value> {
+    let Value::Object(map) = value else {
+        return Err(Error::manifest("清单的最外层必须是键值对"));
+    };
+
+    let is_entry = |key: &String| !["version", MIRRORS_KEY, DEDUP_KEY].contains(&key.as_str());
+    let all_arrays = map.iter().filter(|(key, _)| is_entry(key)).all(|(_, value)| value.is_array());
+    let any_arrays = map.iter().filter(|(key, _)| is_entry(key)).any(|(_, value)| value.is_array());
+    if !any_arrays {
+        return Ok(Value::Object(map));
+    }
+    if !all_arrays {
+        return Err(Error::manifest("清单中同时出现了一层结构和两层结构的 key"));
+    }
+
+    let mut sections = serde_json::Map::new();
+    for (key, value) in map {
+        if !is_entry(&key) {
+            sections.insert(key, value);
+            continue;
+        }
+        let section = sections
+            .entry(infer_format(&key))
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+        if let Value::Object(section) = section {
+            section.insert(key, value);
+        }
+    }
+    Ok(Value::Object(sections))
+}
+
+// 清单中声明镜像模板的字段
+const MIRRORS_KEY: &str = "mirrors";
+// 清单中去重选项的字段
+const DEDUP_KEY: &str = "dedup";
+
+// 镜像模板：名称 → 按顺序排列的链接模板，模板中的 `{path}` 等占位符在展开时替换
+type MirrorTemplates = BTreeMap<String, Vec<String>>;
+
+// 取出（两层结构）清单中的镜像模板，把引用模板的条目展开为多个链接
+fn expand_mirrors(value: &mut Value) -> Result<()> {
+    let Value::Object(map) = value else {
+        return Ok(());
+    };
+    let templates: MirrorTemplates = match map.remove(MIRRORS_KEY) {
+        Some(templates) => serde_json::from_value(templates)
+            .map_err(|err| Error::manifest(format!("镜像模板必须是“名称 → 链接模板列表”：{}", err)))?,
+        None => MirrorTemplates::new(),
+    };
+    for (key, entries) in map.values_mut().filter_map(Value::as_object_mut).flat_map(|section| section.iter_mut()) {
+        let Value::Array(entries) = entries else {
+            continue;
+        };
+        let mut expanded = Vec::with_capacity(entries.len());
+        for entry in entries.drain(..) {
+            match entry {
+                Value::Object(options) if options.contains_key(MIRRORS_KEY) => {
+                    expanded.extend(expand_entry(&templates, options).map_err(|err| in_key(err, key))?)
+                }
+                other => expanded.push(other),
+            }
+        }
+        *entries = expanded;
+    }
+    Ok(())
+}
+
+// 展开一个引用镜像模板的条目：`{ mirrors: 名称, path: …, vars: {…}, 其它选项 }`
+fn expand_entry(templates: &MirrorTemplates, mut options: serde_json::Map<String, Value>) -> Result<Vec<Value>> {
+    if options.contains_key("url") {
+        return Err(Error::manifest("引用镜像模板的条目不能再指定 url"));
+    }
+    let name = match options.remove(MIRRORS_KEY) {
+        Some(Value::String(name)) => name,
+        _ => return Err(Error::manifest("mirrors 必须是镜像模板的名称")),
+    };
+    let urls = templates.get(&name).ok_or_else(|| Error::manifest(format!("没有名为 {} 的镜像模板", name)))?;
+
+    let mut vars: BTreeMap<String, String> = match options.remove("vars") {
+        Some(vars) => serde_json::from_value(vars).map_err(|_| Error::manifest("vars 必须是“名称 → 字符串”"))?,
+        None => BTreeMap::new(),
+    };
+    match options.remove("path") {
+        Some(Value::String(path)) => {
+            vars.insert("path".to_string(), path.trim_start_matches('/').to_string());
+        }
+        Some(_) => return Err(Error::manifest("path 必须是字符串")),
+        None => {}
+    }
+
+    urls.iter()
+        .map(|template| {
+            let mut entry = options.clone();
+            entry.insert("url".to_string(), Value::String(fill_template(template, &vars, &name)?));
+            Ok(Value::Object(entry))
+        })
+        .collect()
+}
+
+// 把链接模板中的 `{名称}` 替换为对应的值
+fn fill_template(template: &str, vars: &BTreeMap<String, String>, name: &str) -> Result<String> {
+    let mut url = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::manifest(format!("镜像模板 {} 中的 {} 缺少 }}", name, template)))?;
+        let var = &rest[start + 1..start + end];
+        let value = vars
+            .get(var)
+            .ok_or_else(|| Error::manifest(format!("镜像模板 {} 中的 {{{}}} 没有指定", name, var)))?;
+        url.push_str(&rest[..start]);
+        url.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    url.push_str(rest);
+    Ok(url)
+}
+
+// 在清单错误信息前加上 key 名
+fn in_key(err: Error, key: &str) -> Error {
+    match err {
+        Error::Manifest(msg) => Error::Manifest(format!("{}：{}", key, msg)),
+        other => other,
+    }
+}
+
+/// 根据 key 名推断一层结构清单中内容的格式：`clash` 开头的是 yaml，其它是 json。
+pub fn infer_format(key: &str) -> &'static str {
+    if key.to_lowercase().starts_with("clash") {
+        "yaml"
+    } else {
+        "json"
+    }
+}
+
+// 读取输入文件，失败时视为清单有误
+fn read_input(file_path: &str) -> Result<String> {
+    fs::read_to_string(file_path).map_err(|err| Error::manifest(format!("读取文件{}失败：{}", file_path, err)))
+}
+
+// 在清单错误信息前加上文件名
+fn in_file(err: Error, file_path: &str) -> Error {
+    match err {
+        Error::Manifest(msg) => Error::Manifest(format!("{}：{}", file_path, msg)),
+        other => other,
+    }
+}
+
+/// 读取文本文件中的链接（每行一个，忽略空行）。
+pub fn read_url_list(file_path: &str) -> Result<Vec<String>> {
+    let content = read_input(file_path)?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// 解析一层结构的 JSON 清单（`key → urls`）。
+pub fn parse_flat_json(json_content: &str) -> Result<FlatManifest> {
+    let json: Value = serde_json::from_str(json_content).map_err(Error::manifest)?;
+    let object = json.as_object().ok_or_else(|| Error::manifest("无效的 JSON 格式"))?;
+
+    let mut tasks = FlatManifest::new();
+    for (task_name, value) in object {
+        let urls = value
+            .as_array()
+            .ok_or_else(|| Error::manifest(format!("任务 '{}' 中的无效 URL 列表", task_name)))?
+            .iter()
+            .map(|url_value| {
+                url_value
+                    .as_str()
+                    .map(ToString::to_string)
+                    .ok_or_else(|| Error::manifest(format!("任务 '{}' 中的无效 URL", task_name)))
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        tasks.insert(task_name.to_string(), urls);
+    }
+    Ok(tasks)
+}
+
+/// 读取一层结构的 JSON 清单文件，文件不存在时返回空清单。
+pub fn read_flat_json_file(file_path: &str) -> Result<FlatManifest> {
+    if !Path::new(file_path).exists() {
+        return Ok(FlatManifest::new());
+    }
+    parse_flat_json(&read_input(file_path)?).map_err(|err| in_file(err, file_path))
+}
+
+/// 将 `urls` 写入一层结构 JSON 清单文件的 `key` 键中（已存在的键会被覆盖）。
+pub fn update_flat_json_file(file_path: &str, key: &str, urls: Vec<String>) -> Result<()> {
+    let mut json_data = read_flat_json_file(file_path)?;
+    json_data.insert(key.to_string(), urls);
+    fs::write(file_path, serde_json::to_string_pretty(&json_data).map_err(Error::manifest)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATES: &str = "
+mirrors:
+  pac2:
+    - https://a.example/{path}
+    - https://b.example/{branch}/{path}
+";
+
+    // 解析 YAML 清单，返回 `json.key` 下所有链接
+    fn urls(yaml: &str, key: &str) -> Result<Vec<String>> {
+        let manifest = parse_manifest(yaml, Some(ManifestFormat::Yaml))?;
+        Ok(manifest.sections["json"][key].iter().map(|source| source.url.clone()).collect())
+    }
+
+    fn manifest_error(result: Result<Vec<String>>) -> String {
+        match result {
+            Err(Error::Manifest(msg)) => msg,
+            other => panic!("应该是清单错误：{:?}", other),
+        }
+    }
+
+    #[test]
+    fn expands_templates_with_path_and_vars() {
+        let yaml = format!(
+            "{}json:\n  singbox:\n{}{}",
+            TEMPLATES,
+            "    - { mirrors: pac2, path: /singbox/config.json, vars: { branch: master }, priority: 5 }\n",
+            "    - https://c.example/config.json\n"
+        );
+        assert_eq!(
+            urls(&yaml, "singbox").unwrap(),
+            [
+                "https://a.example/singbox/config.json",
+                "https://b.example/master/singbox/config.json",
+                "https://c.example/config.json",
+            ]
+        );
+        let manifest = parse_manifest(&yaml, Some(ManifestFormat::Yaml)).unwrap();
+        let priorities: Vec<i32> = manifest.sections["json"]["singbox"].iter().map(|source| source.priority).collect();
+        assert_eq!(priorities[..2], [5, 5]);
+    }
+
+    #[test]
+    fn rejects_unknown_variable() {
+        let yaml = format!("{}json:\n  singbox:\n    - {{ mirrors: pac2, path: config.json }}\n", TEMPLATES);
+        let msg = manifest_error(urls(&yaml, "singbox"));
+        assert!(msg.starts_with("singbox："), "{}", msg);
+        assert!(msg.contains("{branch}"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_unclosed_placeholder() {
+        let yaml = "mirrors:\n  bad: ['https://a.example/{path']\njson:\n  k:\n    - { mirrors: bad, path: x }\n";
+        assert!(manifest_error(urls(yaml, "k")).contains("缺少 }"));
+    }
+
+    #[test]
+    fn rejects_url_together_with_mirrors() {
+        let yaml = format!("{}json:\n  k:\n    - {{ mirrors: pac2, url: 'https://x.example/' }}\n", TEMPLATES);
+        assert!(manifest_error(urls(&yaml, "k")).contains("不能再指定 url"));
+    }
+
+    #[test]
+    fn rejects_unknown_template() {
+        let yaml = format!("{}json:\n  k:\n    - {{ mirrors: nope, path: x }}\n", TEMPLATES);
+        assert!(manifest_error(urls(&yaml, "k")).contains("没有名为 nope 的镜像模板"));
+    }
+
+    // 仓库中自带的清单
+    fn bundled(name: &str) -> String {
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join(name)).unwrap()
+    }
+
+    #[test]
+    fn bundled_manifests_load_identically() {
+        let json = load_manifest(&format!("{}/urls.json", env!("CARGO_MANIFEST_DIR")), None).unwrap();
+        let yaml = load_manifest(&format!("{}/urls.yaml", env!("CARGO_MANIFEST_DIR")), None).unwrap();
+        assert_eq!(json.version, 1);
+        assert_eq!(json.sections, yaml.sections);
+        assert_eq!(json.sections["yaml"]["clashB"][0].priority, 0);
+        assert!(json.sections["json"]["xray"].iter().all(|source| source.enabled && source.timeout.is_none()));
+
+        // 不指定格式时根据内容判断
+        let sniffed = parse_manifest(&bundled("urls.yaml"), None).unwrap();
+        assert_eq!(sniffed.sections, json.sections);
+    }
+
+    #[test]
+    fn flat_and_toml_manifests_match_two_level() {
+        let two_level = parse_manifest(&bundled("urls.json"), None).unwrap();
+        let value: Value = serde_json::from_str(&bundled("urls.json")).unwrap();
+
+        // 一层结构：key 名以 clash 开头的归入 yaml，其它归入 json
+        let flat: serde_json::Map<String, Value> = value
+            .as_object()
+            .unwrap()
+            .values()
+            .flat_map(|section| section.as_object().unwrap().clone())
+            .collect();
+        let flat = parse_manifest(&Value::Object(flat).to_string(), None).unwrap();
+        assert_eq!(flat.sections, two_level.sections);
+
+        let toml = toml::to_string(&value).unwrap();
+        assert_eq!(ManifestFormat::sniff(&toml), ManifestFormat::Toml);
+        assert_eq!(parse_manifest(&toml, None).unwrap().sections, two_level.sections);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_newer_versions() {
+        use crate::report::{conclude, RunStatus};
+
+        let unknown = "{\"json\": {\"k\": [{\"url\": \"https://x.example/a.json\", \"timout\": 5}]}}";
+        let err = parse_manifest(unknown, None).unwrap_err();
+        assert!(err.to_string().contains("timout"), "{}", err);
+        assert_eq!(conclude(Err(err)), RunStatus::BadManifest);
+        assert_eq!(RunStatus::BadManifest.code(), 3);
+
+        let newer = "version: 3\njson:\n  k: [https://x.example/a.json]\n";
+        let err = parse_manifest(newer, Some(ManifestFormat::Yaml)).unwrap_err();
+        assert!(err.to_string().contains("清单版本 3 过新"), "{}", err);
+        assert_eq!(conclude(Err(err)).code(), 3);
+        assert!(parse_manifest("version: 2\njson:\n  k: [https://x.example/a.json]\n", None).is_ok());
+    }
+
+    #[test]
+    fn fills_template_without_placeholders() {
+        let vars = BTreeMap::from([("path".to_string(), "a/b".to_string())]);
+        assert_eq!(fill_template("https://x.example/", &vars, "t").unwrap(), "https://x.example/");
+        assert_eq!(fill_template("https://x.example/{path}?v=1", &vars, "t").unwrap(), "https://x.example/a/b?v=1");
+    }
+}
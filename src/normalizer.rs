@@ -0,0 +1,84 @@
+//! 格式化（规范化）下载到的内容，便于去重和保存。
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// 格式化 JSON，让其适当的缩进和换行。
+///
+/// `json_str` 不是合法的 JSON 时返回 [`Error::Validate`]。
+pub fn format_json(json_str: &str) -> Result<String> {
+    let value: Value = serde_json::from_str(json_str)
+        .map_err(|err| Error::Validate(format!("不是合法的 JSON（{}）", err)))?;
+    serde_json::to_string_pretty(&value).map_err(|err| Error::Validate(err.to_string()))
+}
+
+/// 格式化 YAML：解析后按统一的风格重新输出（保留键的顺序，注释和锚点不保留，
+/// 合并键 `<<` 照常输出），Clash.Meta 等使用 YAML 的程序可以直接读取。
+///
+/// 按 YAML 1.2 解析和输出：像数字、`null` 的字符串（如 `'0443'`）保留引号，
+/// 但 `'on'`、`'yes'` 这类只在 YAML 1.1 中是布尔值的字符串输出时不带引号
+/// （解析时已经分不出原来有没有引号）。Clash.Meta 按字段的类型读取，不受影响；
+/// 按 YAML 1.1 读取的程序会把它们读成布尔值，需要保留引号时在来源上指定 `keep_original`。
+///
+/// `yaml_str` 不是合法的 YAML 时返回 [`Error::Validate`]。
+pub fn format_yaml(yaml_str: &str) -> Result<String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(yaml_str)
+        .map_err(|err| Error::Validate(format!("不是合法的 YAML（{}）", err)))?;
+    serde_yaml::to_string(&value).map_err(|err| Error::Validate(err.to_string()))
+}
+
+/// 按清单中的格式（`json` / `yaml`）规范化内容，去掉首尾空白。
+///
+/// 其它格式原样返回（同样去掉首尾空白）。
+pub fn normalize_content(content: &str, data_file: &str) -> Result<String> {
+    let normalized = match data_file.trim().to_lowercase().as_str() {
+        "json" => format_json(content)?,
+        "yaml" | "yml" => format_yaml(content)?,
+        _ => content.to_string(),
+    };
+    Ok(normalized.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASH: &str = "\
+base: &base
+  type: ss
+  port: 443   # 注释
+proxies:
+  - <<: *base
+    name: 'on'
+    password: '0443'
+    udp: true
+";
+
+    #[test]
+    fn yaml_round_trips() {
+        let formatted = format_yaml(CLASH).unwrap();
+        let parse = |text: &str| serde_yaml::from_str::<serde_yaml::Value>(text).unwrap();
+        assert_eq!(parse(&formatted), parse(CLASH));
+        assert_eq!(format_yaml(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn yaml_keeps_merge_keys_and_quoted_numbers() {
+        let formatted = format_yaml(CLASH).unwrap();
+        // 锚点展开，合并键保留
+        assert!(formatted.contains("- <<:\n    type: ss\n    port: 443\n"), "{}", formatted);
+        assert!(formatted.contains("password: '0443'"), "{}", formatted);
+        assert!(!formatted.contains('&') && !formatted.contains('#'), "{}", formatted);
+        // YAML 1.2 中 `on` 是字符串，输出时不带引号（见 `format_yaml` 的说明）
+        assert!(formatted.contains("name: on\n"), "{}", formatted);
+    }
+
+    #[test]
+    fn normalizes_by_format() {
+        assert_eq!(normalize_content("{\"a\":[1,2]}", "JSON").unwrap(), "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+        assert_eq!(normalize_content("a:   1\n\n", "yml").unwrap(), "a: 1");
+        assert_eq!(normalize_content("  vmess://abc \n", "txt").unwrap(), "vmess://abc");
+        assert!(matches!(normalize_content("a: [", "yaml"), Err(Error::Validate(_))));
+    }
+}
@@ -0,0 +1,174 @@
+//! 输出文件的编号：同一个 key 有多份不同的内容时，按稳定的顺序编号（`xray_1.json`、`xray_2.json` ……），
+//! 并记住每份内容的编号，内容不变时下次运行仍然使用同一个编号。
+//!
+//! 编号记录在输出文件夹的 `.dlconf-numbering.json` 中。
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::error::{Error, Result};
+use crate::writer::write_atomic;
+
+/// 保存编号的文件（在输出文件夹中）。
+pub const NUMBERING_FILE: &str = ".dlconf-numbering.json";
+
+/// 新出现的内容的编号顺序。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberingOrder {
+    /// 按镜像的优先级顺序（第一次下载到这份内容的镜像）。
+    #[default]
+    Priority,
+    /// 按内容哈希。
+    Hash,
+}
+
+impl FromStr for NumberingOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "priority" => Ok(Self::Priority),
+            "hash" => Ok(Self::Hash),
+            other => Err(format!("不支持的编号顺序：{}（可选 priority / hash）", other)),
+        }
+    }
+}
+
+/// 一个输出文件夹中所有输出文件的编号：输出文件名（如 `xray.json`）→ 内容的 key → 编号。
+#[derive(Debug)]
+pub struct Numbering {
+    path: PathBuf,
+    order: NumberingOrder,
+    outputs: Mutex<BTreeMap<String, BTreeMap<String, usize>>>,
+}
+
+impl Numbering {
+    /// 读取输出文件夹中的编号文件；文件不存在时从空白开始，无法读取或解析时打印警告后从空白开始。
+    pub fn load(output: &str, order: NumberingOrder) -> Self {
+        let path = Path::new(output).join(NUMBERING_FILE);
+        let outputs = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(err) => Err(err.to_string()),
+        };
+        let outputs = outputs.unwrap_or_else(|err| {
+            eprintln!("编号文件 {} 无法读取，重新编号（{}）", path.display(), err);
+            BTreeMap::new()
+        });
+        Self { path, order, outputs: Mutex::new(outputs) }
+    }
+
+    /// 写回编号文件（没有变化时不写入）。
+    pub fn save(&self) -> Result<()> {
+        let outputs = self.outputs.lock().unwrap();
+        let text = serde_json::to_string_pretty(&*outputs).map_err(|err| Error::Io(err.into()))?;
+        write_atomic(&self.path, text.as_bytes())?;
+        Ok(())
+    }
+
+    /// 输出文件 `name` 目前使用的编号（从小到大）。
+    pub fn numbers(&self, name: &str) -> Vec<usize> {
+        let outputs = self.outputs.lock().unwrap();
+        let mut numbers: Vec<usize> = outputs.get(name).into_iter().flat_map(|keys| keys.values().copied()).collect();
+        numbers.sort_unstable();
+        numbers
+    }
+
+    /// 给输出文件 `name` 的各份内容编号（从 1 开始），`keys` 为内容的 key，按镜像的优先级顺序排列。
+    ///
+    /// 上次运行已经有编号的内容保留原来的编号；新的内容按编号顺序使用最小的空闲编号。
+    /// 本次没有出现的内容的编号被释放。只有一份内容时文件名不加编号，编号记为 1。
+    pub fn assign(&self, name: &str, keys: &[String]) -> Vec<usize> {
+        let mut outputs = self.outputs.lock().unwrap();
+        let previous = outputs.remove(name).unwrap_or_default();
+        if let [key] = keys {
+            outputs.insert(name.to_string(), BTreeMap::from([(key.clone(), 1)]));
+            return vec![1];
+        }
+
+        let mut assigned: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut used = BTreeSet::new();
+        for key in keys {
+            if let Some(&number) = previous.get(key).filter(|number| !used.contains(*number)) {
+                assigned.insert(key, number);
+                used.insert(number);
+            }
+        }
+
+        let mut new_keys: Vec<&String> = keys.iter().filter(|key| !assigned.contains_key(key.as_str())).collect();
+        if self.order == NumberingOrder::Hash {
+            new_keys.sort();
+        }
+        let mut next = 1;
+        for key in new_keys {
+            while used.contains(&next) {
+                next += 1;
+            }
+            assigned.insert(key, next);
+            used.insert(next);
+        }
+
+        let numbers = keys.iter().map(|key| assigned[key.as_str()]).collect();
+        outputs.insert(name.to_string(), assigned.into_iter().map(|(key, number)| (key.to_string(), number)).collect());
+        numbers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbering(order: NumberingOrder) -> Numbering {
+        Numbering { path: PathBuf::new(), order, outputs: Mutex::default() }
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_numbers_when_order_changes() {
+        let numbering = numbering(NumberingOrder::Priority);
+        assert_eq!(numbering.assign("xray.json", &keys(&["a", "b", "c"])), [1, 2, 3]);
+        assert_eq!(numbering.assign("xray.json", &keys(&["c", "a", "b"])), [3, 1, 2]);
+    }
+
+    #[test]
+    fn releases_and_reuses_numbers() {
+        let numbering = numbering(NumberingOrder::Priority);
+        numbering.assign("xray.json", &keys(&["a", "b", "c"]));
+        assert_eq!(numbering.assign("xray.json", &keys(&["a", "c"])), [1, 3]);
+        assert_eq!(numbering.numbers("xray.json"), [1, 3]);
+        // 新的内容使用释放的最小编号
+        assert_eq!(numbering.assign("xray.json", &keys(&["d", "a", "c", "e"])), [2, 1, 3, 4]);
+    }
+
+    #[test]
+    fn single_content_is_numbered_one() {
+        let numbering = numbering(NumberingOrder::Priority);
+        numbering.assign("xray.json", &keys(&["a", "b"]));
+        assert_eq!(numbering.assign("xray.json", &keys(&["b"])), [1]);
+        assert_eq!(numbering.assign("xray.json", &keys(&["a", "b"])), [2, 1]);
+    }
+
+    #[test]
+    fn numbers_new_contents_by_hash() {
+        let by_priority = numbering(NumberingOrder::Priority);
+        assert_eq!(by_priority.assign("xray.json", &keys(&["f0", "0a"])), [1, 2]);
+        let by_hash = numbering(NumberingOrder::Hash);
+        assert_eq!(by_hash.assign("xray.json", &keys(&["f0", "0a"])), [2, 1]);
+        // 已有编号的内容不受影响
+        assert_eq!(by_hash.assign("xray.json", &keys(&["f0", "9b", "0a", "1c"])), [2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn outputs_are_numbered_separately() {
+        let numbering = numbering(NumberingOrder::Priority);
+        numbering.assign("xray.json", &keys(&["a", "b"]));
+        assert_eq!(numbering.assign("xray.yaml", &keys(&["b", "a"])), [1, 2]);
+    }
+}
@@ -0,0 +1,163 @@
+//! 下载流程：并发下载同一个 key 下的所有链接，规范化后去重。
+//!
+//! 所有内容都边下载边写入临时文件（有大小上限），不会整个读入内存，并在处理前校验
+//! （见 [`validate`](crate::validate)）。文本内容解码、规范化后按语义去重（见 [`dedup`](crate::dedup)）；二进制内容（`.dat`、`.mmdb`、`.srs` 等）不解码，
+//! 保留临时文件，按内容哈希去重。
+
+use futures::future::join_all;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::time::Duration;
+
+use crate::client::HttpClient;
+use crate::dedup::{dedup_key, DedupOptions};
+use crate::error::{Error, Result};
+use crate::fetcher::{decode_source, default_timeout, fetch_source_to_file};
+use crate::hash::{content_hash, file_hash};
+use crate::manifest::{ContentMode, Source};
+use crate::normalizer::normalize_content;
+use crate::report::ItemReport;
+use crate::validate::{validate_binary_file, validate_text, Quarantine};
+use crate::writer::claim_temp_file;
+
+/// 一个 key 下去重后的内容。
+#[derive(Debug, Default)]
+pub struct UniqueContents {
+    /// 规范化后的文本内容（来源要求保留原始内容时为服务器返回的原始字节）：
+    /// 去重 key → 内容（相同的内容只保留第一份）。
+    pub texts: BTreeMap<String, Vec<u8>>,
+    /// 二进制内容：内容哈希 → 保存内容的临时文件。
+    pub files: BTreeMap<String, PathBuf>,
+    /// 所有内容的 key（文本为去重 key，二进制为内容哈希），按第一次下载到它的来源的顺序排列。
+    pub order: Vec<String>,
+}
+
+impl UniqueContents {
+    pub fn len(&self) -> usize {
+        self.texts.len() + self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 删除还没有移动到位的临时文件。
+    pub fn discard_files(&self) {
+        for temp_file in self.files.values() {
+            let _ = fs::remove_file(temp_file);
+        }
+    }
+}
+
+// 一个来源下载并处理后的内容，以及原始内容的哈希
+enum Content {
+    Text { body: Vec<u8>, key: String, hash: String },
+    File { path: PathBuf, hash: String },
+}
+
+/// 下载与处理数据，返回去重后的内容（按 `sources` 的顺序记录每份内容第一次出现的位置）。
+///
+/// 所有链接并发下载，同时进行的下载数受客户端的并发限制。
+/// 每个来源按自己的处理方式（文本 / 二进制）和格式（没有指定时为 `data_file`）校验、处理内容，
+/// 临时文件保存在 `output` 中（每个来源一个，见 [`claim_temp_file`]），没有通过校验的内容隔离到 `output/.rejected/` 中。
+/// 文本内容按 `dedup` 忽略部分路径后比较。
+/// 每个链接的结果记录到 `report` 中，下载或规范化失败的链接同时打印到标准错误输出，
+/// 不会影响其它链接。
+pub async fn download_and_process_data(
+    client: &HttpClient,
+    sources: &[&Source],
+    inner_key: &str,
+    data_file: &str,
+    output: &str,
+    dedup: &DedupOptions,
+    report: &mut ItemReport,
+) -> UniqueContents {
+    let quarantine = Quarantine::new(output, inner_key);
+    // 每个来源占用一个新的临时文件，不同区段中同名的 key 并发处理时也不会共用
+    let temp_name = format!("{}.{}", inner_key, data_file);
+    let tasks = sources.iter().map(|source| {
+        let temp_name = &temp_name;
+        async move {
+            match claim_temp_file(Path::new(output), temp_name) {
+                Ok(temp_path) => fetch_content(client, source, data_file, temp_path, quarantine, dedup).await,
+                Err(err) => (Err(err), Duration::ZERO),
+            }
+        }
+    });
+    let results = join_all(tasks).await;
+
+    let mut unique_contents = UniqueContents::default();
+    for (source, (result, elapsed)) in sources.iter().zip(results) {
+        match result {
+            Ok(Content::Text { body, key, hash }) => {
+                if !unique_contents.texts.contains_key(&key) {
+                    unique_contents.order.push(key.clone());
+                    unique_contents.texts.insert(key, body);
+                }
+                report.record_fetched(&source.url, elapsed, hash);
+            }
+            Ok(Content::File { path, hash }) => {
+                // 内容相同的二进制文件只保留一份
+                if unique_contents.files.contains_key(&hash) {
+                    let _ = fs::remove_file(&path);
+                } else {
+                    unique_contents.order.push(hash.clone());
+                    unique_contents.files.insert(hash.clone(), path);
+                }
+                report.record_fetched(&source.url, elapsed, hash);
+            }
+            Err(err) => {
+                eprintln!("{}配置文件，{} - {}", inner_key, source.url, err);
+                report.record_timed(&source.url, Some(err), elapsed);
+            }
+        }
+    }
+    unique_contents
+}
+
+// 下载一个来源：先边下载边写入临时文件 `temp_path`，再按处理方式（不确定时看 `Content-Type`）
+// 校验、处理：文本读出后解码、校验、规范化（来源要求保留原始内容时不规范化），二进制校验后保留临时文件；没有通过校验的内容隔离起来
+async fn fetch_content(
+    client: &HttpClient,
+    source: &Source,
+    data_file: &str,
+    temp_path: PathBuf,
+    quarantine: Quarantine<'_>,
+    dedup: &DedupOptions,
+) -> (Result<Content>, Duration) {
+    let format = source.format_or(data_file);
+    let mode = source.mode_or(data_file);
+    let (result, elapsed) = fetch_source_to_file(client, source, default_timeout(mode), &temp_path).await;
+    let content = result.and_then(|fetched| {
+        match mode.unwrap_or_else(|| ContentMode::for_content_type(fetched.content_type.as_deref())) {
+            ContentMode::Binary => {
+                if let Err(err) = validate_binary_file(&temp_path) {
+                    if matches!(err, Error::Validate(_)) {
+                        quarantine.file(&source.url, &temp_path, &err);
+                    }
+                    return Err(err);
+                }
+                Ok(Content::File { hash: file_hash(&temp_path)?, path: temp_path.clone() })
+            }
+            ContentMode::Text => {
+                let bytes = fs::read(&temp_path)?;
+                fs::remove_file(&temp_path)?;
+                let content = decode_source(source, &bytes, fetched.content_type.as_deref())?;
+                if let Err(err) = validate_text(&content, format) {
+                    quarantine.bytes(&source.url, &bytes, &err);
+                    return Err(err);
+                }
+                let key = dedup_key(&content, format, dedup);
+                let hash = content_hash(&bytes);
+                // 保留原始内容时写入服务器返回的原始字节（不转换字符集、不去掉 BOM）
+                let body = if source.keep_original { bytes } else { normalize_content(&content, format)?.into_bytes() };
+                Ok(Content::Text { body, key, hash })
+            }
+        }
+    });
+    if content.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    (content, elapsed)
+}
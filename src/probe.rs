@@ -0,0 +1,133 @@
+//! 预检：选出最优链接前，先确认镜像可用（`HEAD` 请求，或者只取开头几个字节的 `GET` 请求）。
+//!
+//! 有的 CDN 不支持（或者错误处理）`HEAD` 请求，自动模式下 `HEAD` 被拒绝时改用范围 `GET`，
+//! 并记下每个站点支持的预检方式，之后的运行直接使用。
+
+use reqwest::header::RANGE;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::client::HttpClient;
+use crate::error::{Error, Result};
+use crate::fetcher::{with_source_options, TEXT_TIMEOUT};
+use crate::limiter::host_key;
+use crate::manifest::Source;
+
+/// 预检的方式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeMode {
+    /// 优先使用站点记录的方式，没有记录时先 `HEAD`，被拒绝时改用范围 `GET`。
+    #[default]
+    Auto,
+    /// `HEAD` 请求。
+    Head,
+    /// 只取第一个字节的 `GET` 请求（`Range: bytes=0-0`）。
+    Range,
+    /// 不预检，直接下载。
+    None,
+}
+
+impl FromStr for ProbeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "head" => Ok(Self::Head),
+            "range" => Ok(Self::Range),
+            "none" => Ok(Self::None),
+            other => Err(format!("不支持的预检方式：{}（可选 auto / head / range / none）", other)),
+        }
+    }
+}
+
+impl ProbeMode {
+    fn label(self) -> &'static str {
+        match self {
+            ProbeMode::Auto => "auto",
+            ProbeMode::Head => "HEAD",
+            ProbeMode::Range => "范围 GET",
+            ProbeMode::None => "none",
+        }
+    }
+}
+
+/// 每个站点（`主机:端口`）支持的预检方式，保存在镜像健康度的状态文件中。
+#[derive(Debug, Default)]
+pub struct HostProbes {
+    hosts: Mutex<BTreeMap<String, ProbeMode>>,
+    changed: AtomicBool,
+}
+
+impl HostProbes {
+    pub fn new(hosts: BTreeMap<String, ProbeMode>) -> Self {
+        Self { hosts: Mutex::new(hosts), changed: AtomicBool::new(false) }
+    }
+
+    /// 站点记录的预检方式。
+    pub fn get(&self, host: &str) -> Option<ProbeMode> {
+        self.hosts.lock().unwrap().get(host).copied()
+    }
+
+    /// 记录站点支持的预检方式。
+    pub fn record(&self, host: &str, mode: ProbeMode) {
+        if self.hosts.lock().unwrap().insert(host.to_string(), mode) != Some(mode) {
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// 所有站点的记录。
+    pub fn snapshot(&self) -> BTreeMap<String, ProbeMode> {
+        self.hosts.lock().unwrap().clone()
+    }
+
+    /// 是否有新的记录。
+    pub fn is_changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed)
+    }
+}
+
+/// 按 `mode` 预检来源，确认镜像可用。
+///
+/// 自动模式下，按站点记录的方式（没有记录时为 `HEAD`）预检；服务器拒绝（返回错误状态码）时
+/// 改用另一种方式，成功的方式记录到 `hosts` 中。连接失败时直接返回错误。
+pub async fn probe(client: &HttpClient, source: &Source, mode: ProbeMode, hosts: &HostProbes) -> Result<()> {
+    match mode {
+        ProbeMode::None => Ok(()),
+        ProbeMode::Head | ProbeMode::Range => probe_with(client, source, mode).await,
+        ProbeMode::Auto => {
+            let host = host_key(&source.url);
+            let first = hosts.get(&host).filter(|known| *known == ProbeMode::Range).unwrap_or(ProbeMode::Head);
+            let second = if first == ProbeMode::Head { ProbeMode::Range } else { ProbeMode::Head };
+            let mode = match probe_with(client, source, first).await {
+                Ok(()) => first,
+                Err(Error::HttpStatus { status, .. }) => {
+                    println!("  - {} 拒绝了 {} 预检（状态码 {}），改用 {}", host, first.label(), status, second.label());
+                    probe_with(client, source, second).await?;
+                    second
+                }
+                Err(err) => return Err(err),
+            };
+            hosts.record(&host, mode);
+            Ok(())
+        }
+    }
+}
+
+// 用指定的方式预检一次（超时时间为来源的 `timeout`），只看状态码，不读取内容
+async fn probe_with(client: &HttpClient, source: &Source, mode: ProbeMode) -> Result<()> {
+    let request = match mode {
+        ProbeMode::Range => client.get(&source.url).header(RANGE, "bytes=0-0"),
+        _ => client.head(&source.url),
+    };
+    let request = with_source_options(client, request, source)?.timeout(source.timeout_or(TEXT_TIMEOUT));
+    let response = client.send(request).await?;
+    if !response.status().is_success() {
+        return Err(Error::from_status(&response));
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+//! 运行报告：记录每个链接的下载结果，并汇总为退出码。
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use crate::client::ConnectionStats;
+use crate::error::{Error, Result};
+use crate::writer::WriteOutcome;
+
+/// 单个链接的下载结果。
+#[derive(Debug)]
+pub struct UrlOutcome {
+    pub url: String,
+    /// 失败原因，成功时为 `None`。
+    pub error: Option<Error>,
+    /// 这个链接花费的时间（有记录时）。
+    pub elapsed: Option<Duration>,
+    /// 下载到的内容的哈希（有记录时）。
+    pub content_hash: Option<String>,
+}
+
+/// 写入（或者内容没有变化、没有写入）的一个输出文件。
+#[derive(Debug)]
+pub struct WrittenFile {
+    pub path: String,
+    pub outcome: WriteOutcome,
+}
+
+/// 一个下载任务（一个 key，或 `url.txt` 中的一个链接）及其所有链接的结果。
+#[derive(Debug)]
+pub struct ItemReport {
+    pub name: String,
+    pub attempts: Vec<UrlOutcome>,
+    /// 这个任务的输出文件。
+    pub files: Vec<WrittenFile>,
+    /// 与具体链接无关的失败（例如写入文件失败）。
+    pub error: Option<Error>,
+}
+
+impl ItemReport {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), attempts: Vec::new(), files: Vec::new(), error: None }
+    }
+
+    pub fn record_ok(&mut self, url: &str) {
+        self.attempts.push(UrlOutcome { url: url.to_string(), error: None, elapsed: None, content_hash: None });
+    }
+
+    pub fn record_err(&mut self, url: &str, error: Error) {
+        self.attempts.push(UrlOutcome { url: url.to_string(), error: Some(error), elapsed: None, content_hash: None });
+    }
+
+    /// 记录链接的结果和耗时，`error` 为 `None` 表示成功。
+    pub fn record_timed(&mut self, url: &str, error: Option<Error>, elapsed: Duration) {
+        self.attempts.push(UrlOutcome { url: url.to_string(), error, elapsed: Some(elapsed), content_hash: None });
+    }
+
+    /// 记录成功下载的链接、耗时和内容的哈希（见 [`content_hash`](crate::hash::content_hash)）。
+    pub fn record_fetched(&mut self, url: &str, elapsed: Duration, content_hash: String) {
+        self.attempts.push(UrlOutcome {
+            url: url.to_string(),
+            error: None,
+            elapsed: Some(elapsed),
+            content_hash: Some(content_hash),
+        });
+    }
+
+    /// 记录输出文件及其写入结果。
+    pub fn record_written(&mut self, path: &str, outcome: WriteOutcome) {
+        self.files.push(WrittenFile { path: path.to_string(), outcome });
+    }
+
+    /// 将整个任务记为失败。
+    pub fn fail(&mut self, error: Error) {
+        self.error = Some(error);
+    }
+
+    /// 没有整体失败，并且至少有一个链接成功，任务就算成功。
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.attempts.iter().any(|attempt| attempt.error.is_none())
+    }
+}
+
+/// 一次运行的报告。
+#[derive(Debug, Default)]
+pub struct RunReport {
+    pub items: Vec<ItemReport>,
+    /// 本次运行的连接复用情况。
+    pub connections: ConnectionStats,
+}
+
+impl RunReport {
+    pub fn push(&mut self, item: ItemReport) {
+        self.items.push(item);
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.items.iter().filter(|item| item.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.items.len() - self.succeeded()
+    }
+
+    pub fn status(&self) -> RunStatus {
+        match (self.succeeded(), self.failed()) {
+            (_, 0) => RunStatus::Success,
+            (0, _) => RunStatus::TotalFailure,
+            _ => RunStatus::PartialFailure,
+        }
+    }
+
+    /// 打印汇总信息。
+    pub fn print_summary(&self) {
+        if self.items.is_empty() {
+            return;
+        }
+        println!("汇总：{} 个任务成功，{} 个任务失败", self.succeeded(), self.failed());
+        if self.connections.requests > 0 {
+            println!(
+                "连接：共 {} 个请求，新建 {} 个连接（握手），复用 {} 次",
+                self.connections.requests,
+                self.connections.connections,
+                self.connections.reused()
+            );
+        }
+        if self.connections.cache_hits > 0 {
+            println!("缓存：{} 个链接使用了缓存的内容", self.connections.cache_hits);
+        }
+        for item in self.items.iter().filter(|item| !item.is_ok()) {
+            println!("  - {} 失败", item.name);
+            if let Some(err) = &item.error {
+                println!("      {}", err);
+            }
+            for attempt in &item.attempts {
+                match (&attempt.error, attempt.elapsed) {
+                    (Some(err), Some(elapsed)) => println!("      {} - {}（{:?}）", attempt.url, err, elapsed),
+                    (Some(err), None) => println!("      {} - {}", attempt.url, err),
+                    (None, _) => {}
+                }
+            }
+        }
+        self.print_files();
+    }
+
+    /// 所有有变化（新建、替换或删除）的输出文件。
+    pub fn changed_files(&self) -> impl Iterator<Item = &WrittenFile> {
+        self.items.iter().flat_map(|item| &item.files).filter(|file| file.outcome.is_changed())
+    }
+
+    // 打印输出文件的变化情况，列出有变化的文件
+    fn print_files(&self) {
+        let total: usize = self.items.iter().map(|item| item.files.len()).sum();
+        if total == 0 {
+            return;
+        }
+        let changed = self.changed_files().count();
+        println!("文件：{} 个有变化，{} 个没有变化", changed, total - changed);
+        for file in self.changed_files() {
+            println!("  - {}（{}）", file.path, file.outcome.label());
+        }
+    }
+}
+
+/// 运行结果，对应进程的退出码。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// 0：全部成功
+    Success,
+    /// 1：部分任务失败
+    PartialFailure,
+    /// 2：全部任务失败
+    TotalFailure,
+    /// 3：清单或输入文件有误，无法开始下载
+    BadManifest,
+}
+
+impl RunStatus {
+    pub fn code(self) -> u8 {
+        match self {
+            RunStatus::Success => 0,
+            RunStatus::PartialFailure => 1,
+            RunStatus::TotalFailure => 2,
+            RunStatus::BadManifest => 3,
+        }
+    }
+}
+
+impl From<RunStatus> for ExitCode {
+    fn from(status: RunStatus) -> Self {
+        ExitCode::from(status.code())
+    }
+}
+
+/// 打印子命令的执行结果（汇总或错误），并得到运行结果。
+///
+/// 子命令本身返回错误时没有开始（或无法继续）下载：清单有误时为 [`RunStatus::BadManifest`]，
+/// 其它错误为 [`RunStatus::TotalFailure`]。
+pub fn conclude(result: Result<RunReport>) -> RunStatus {
+    match result {
+        Ok(report) => {
+            report.print_summary();
+            report.status()
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            match err {
+                Error::Manifest(_) => RunStatus::BadManifest,
+                _ => RunStatus::TotalFailure,
+            }
+        }
+    }
+}
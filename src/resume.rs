@@ -0,0 +1,290 @@
+//! 断点续传：下载中途断开时保留 `.part` 文件，之后用 `Range` 请求接着下载。
+//!
+//! `.part` 文件旁边的 `.part.json` 记录链接、`ETag`、`Last-Modified` 和总长度，
+//! 续传时用 `If-Range` 确认服务器上的内容没有变化。没有 `.part` 文件时，和普通的下载一样
+//! 带上缓存的条件请求头（见 [`cache`](crate::cache)）。
+
+use reqwest::header::{HeaderMap, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cache::CacheMeta;
+use crate::client::{check_size, Fetched, HttpClient};
+use crate::error::{Error, Result};
+use crate::fetcher::url_file_name;
+use crate::hash::content_hash;
+
+// `.part` 文件对应的下载信息
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct PartMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// 完整内容的字节数（服务器告知时）。
+    total: Option<u64>,
+    content_type: Option<String>,
+}
+
+impl PartMeta {
+    fn from_headers(url: &str, headers: &HeaderMap, total: Option<u64>) -> Self {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        Self {
+            url: url.to_string(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            total,
+            content_type: header(CONTENT_TYPE),
+        }
+    }
+
+    // `If-Range` 用的验证信息：优先用 `ETag`
+    fn validator(&self) -> Option<&str> {
+        self.etag.as_deref().or(self.last_modified.as_deref())
+    }
+}
+
+/// 下载 `url`，支持断点续传；下载完成后完整的内容在 `part_path` 中。
+///
+/// 已经有 `part_path`（并且是同一个链接）时，用 `Range` 请求接着下载；服务器不支持 `Range`、
+/// 内容已经变化或者范围不对时从头下载。请求失败或下载中途断开时按客户端的重试策略从断点继续
+/// （总的尝试次数不超过重试策略的上限），重试用完后保留 `part_path`，下次运行时接着下载。
+/// 超过大小上限时删除 `part_path`。
+pub async fn download_resumable(
+    client: &HttpClient,
+    url: &str,
+    part_path: &Path,
+    max_size: Option<u64>,
+) -> Result<Fetched> {
+    let meta_path = meta_path(part_path);
+    let cache_key = client.cache_key(&client.get(url).build()?);
+    let mut attempt = 1;
+    loop {
+        match download_once(client, url, &cache_key, part_path, &meta_path, client.body_limit(max_size)).await {
+            Ok(Downloaded::Fresh(meta)) => {
+                let _ = fs::remove_file(&meta_path);
+                let cache_meta = CacheMeta {
+                    url: url.to_string(),
+                    etag: meta.etag,
+                    last_modified: meta.last_modified,
+                    content_type: meta.content_type.clone(),
+                };
+                client.store_file_in_cache(&cache_key, &cache_meta, part_path);
+                let len = fs::metadata(part_path)?.len();
+                return Ok(Fetched { content_type: meta.content_type, len });
+            }
+            Ok(Downloaded::Cached(meta)) => {
+                let _ = fs::remove_file(&meta_path);
+                let len = fs::metadata(part_path)?.len();
+                return Ok(Fetched { content_type: meta.content_type, len });
+            }
+            Err(err @ Error::TooLarge { .. }) => {
+                discard(part_path, &meta_path);
+                return Err(err);
+            }
+            Err(err) => {
+                let Some(delay) = client.retry_policy().next_delay(attempt, &err) else {
+                    return Err(err);
+                };
+                eprintln!("  - {} {}，{:?} 后从断点继续（第 {} 次）", url, err, delay, attempt);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// 一次下载的结果
+enum Downloaded {
+    // 下载（或者接着下载）的内容，已经写入 `.part` 文件
+    Fresh(PartMeta),
+    // 服务器返回 304，缓存的内容已经复制到 `.part` 文件
+    Cached(CacheMeta),
+}
+
+// 发送一次请求，把内容写入（或追加到）`part_path`：有 `.part` 文件时带上 `Range`，
+// 否则有 `cache_key` 对应的缓存时带上条件请求头。无法从断点继续时删除 `.part` 文件，最多从头重新请求一次
+async fn download_once(
+    client: &HttpClient,
+    url: &str,
+    cache_key: &str,
+    part_path: &Path,
+    meta_path: &Path,
+    limit: Option<u64>,
+) -> Result<Downloaded> {
+    let mut restarted = false;
+    loop {
+        let previous = load_meta(meta_path, url).filter(|_| part_path.is_file());
+        let offset = match &previous {
+            Some(_) => fs::metadata(part_path)?.len(),
+            None => 0,
+        };
+        // 只有 `.part` 文件不是空的时才发送 `Range`
+        let ranged = previous.filter(|_| offset > 0);
+
+        let mut request = client.get(url);
+        let mut cached = None;
+        match &ranged {
+            Some(meta) => {
+                request = request.header(RANGE, format!("bytes={}-", offset));
+                if let Some(validator) = meta.validator() {
+                    request = request.header(IF_RANGE, validator);
+                }
+            }
+            None => {
+                cached = client.cached(cache_key, url);
+                if let Some((meta, _)) = &cached {
+                    request = request.headers(meta.conditional_headers());
+                }
+            }
+        }
+        // 只发送一次，失败时由 `download_resumable` 按重试策略从断点继续
+        let mut response = client.send_once(request.build()?).await?;
+        if let (StatusCode::NOT_MODIFIED, Some((meta, body_path))) = (response.status(), cached) {
+            fs::copy(body_path, part_path)?;
+            client.record_cache_hit();
+            return Ok(Downloaded::Cached(meta));
+        }
+
+        let (mut file, mut received, meta) = match (response.status(), ranged) {
+            (StatusCode::PARTIAL_CONTENT, Some(meta)) if continues(&meta, offset, response.headers()) => {
+                println!("  - {} 从第 {} 字节继续下载", url, offset);
+                (OpenOptions::new().append(true).open(part_path)?, offset, meta)
+            }
+            (StatusCode::OK, ranged) => {
+                if ranged.is_some() {
+                    println!("  - {} 服务器没有按范围返回内容，从头下载", url);
+                }
+                let meta = PartMeta::from_headers(url, response.headers(), response.content_length());
+                save_meta(meta_path, &meta)?;
+                (File::create(part_path)?, 0, meta)
+            }
+            // 已经下载完了
+            (StatusCode::RANGE_NOT_SATISFIABLE, Some(meta)) if meta.total == Some(offset) => {
+                return Ok(Downloaded::Fresh(meta))
+            }
+            // 范围不对（或者服务器上的内容已经变化），从头下载；没有发送 `Range` 时
+            // 返回错误，由重试策略决定是否重试
+            (StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE, Some(_)) if !restarted => {
+                println!("  - {} 无法从断点继续，重新下载", url);
+                discard(part_path, meta_path);
+                restarted = true;
+                continue;
+            }
+            _ => return Err(Error::from_status(&response)),
+        };
+
+        check_size(meta.total.unwrap_or(0), limit)?;
+        while let Some(chunk) = response.chunk().await? {
+            received += chunk.len() as u64;
+            check_size(received, limit)?;
+            file.write_all(&chunk)?;
+        }
+        return match meta.total {
+            Some(expected) if received != expected => Err(Error::Incomplete { received, expected }),
+            _ => Ok(Downloaded::Fresh(meta)),
+        };
+    }
+}
+
+/// 链接对应的 `.part` 文件：`save_folder/文件名.哈希.part`（文件名不含查询参数，同名的不同链接不会冲突）。
+pub fn part_path(url: &str, save_folder: &str) -> PathBuf {
+    let hash = content_hash(url.as_bytes());
+    Path::new(save_folder).join(format!("{}.{}.part", url_file_name(url), &hash[..8]))
+}
+
+fn meta_path(part_path: &Path) -> PathBuf {
+    let mut path = part_path.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+// 206 响应是否正好接着 `.part` 文件：起始位置一致，总长度和 `ETag` 没有变化
+fn continues(meta: &PartMeta, offset: u64, headers: &HeaderMap) -> bool {
+    let (start, total) = content_range(headers);
+    let etag = headers.get(ETAG).and_then(|value| value.to_str().ok());
+    start == Some(offset)
+        && (total.is_none() || meta.total.is_none() || total == meta.total)
+        && (etag.is_none() || meta.etag.is_none() || etag == meta.etag.as_deref())
+}
+
+// `Content-Range: bytes 100-199/200` 中的起始位置和总长度
+fn content_range(headers: &HeaderMap) -> (Option<u64>, Option<u64>) {
+    let Some(value) = headers.get(CONTENT_RANGE).and_then(|value| value.to_str().ok()) else {
+        return (None, None);
+    };
+    let Some((range, total)) = value.trim().trim_start_matches("bytes").trim().split_once('/') else {
+        return (None, None);
+    };
+    let start = range.split_once('-').and_then(|(start, _)| start.trim().parse().ok());
+    (start, total.trim().parse().ok())
+}
+
+// 读取 `.part` 文件的下载信息，不是同一个链接时视为没有
+fn load_meta(meta_path: &Path, url: &str) -> Option<PartMeta> {
+    let meta: PartMeta = serde_json::from_str(&fs::read_to_string(meta_path).ok()?).ok()?;
+    (meta.url == url).then_some(meta)
+}
+
+fn save_meta(meta_path: &Path, meta: &PartMeta) -> Result<()> {
+    fs::write(meta_path, serde_json::to_string_pretty(meta).map_err(io::Error::from)?)?;
+    Ok(())
+}
+
+fn discard(part_path: &Path, meta_path: &Path) {
+    let _ = fs::remove_file(part_path);
+    let _ = fs::remove_file(meta_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(reqwest::header::HeaderName, &str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap())).collect()
+    }
+
+    fn meta(etag: Option<&str>, total: Option<u64>) -> PartMeta {
+        PartMeta { etag: etag.map(str::to_string), total, ..PartMeta::default() }
+    }
+
+    #[test]
+    fn part_path_strips_query() {
+        let path = part_path("https://x.example/dir/geo.dat?v=1#top", "out");
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("geo.dat.") && name.ends_with(".part"), "{}", name);
+        assert_eq!(path.parent(), Some(Path::new("out")));
+        assert_ne!(path, part_path("https://x.example/dir/geo.dat?v=2", "out"));
+    }
+
+    #[test]
+    fn parses_content_range() {
+        assert_eq!(content_range(&headers(&[(CONTENT_RANGE, "bytes 100-199/200")])), (Some(100), Some(200)));
+        assert_eq!(content_range(&headers(&[(CONTENT_RANGE, "bytes 0-99/*")])), (Some(0), None));
+        assert_eq!(content_range(&headers(&[(CONTENT_RANGE, "bytes */200")])), (None, Some(200)));
+        assert_eq!(content_range(&headers(&[(CONTENT_RANGE, "garbage")])), (None, None));
+        assert_eq!(content_range(&HeaderMap::new()), (None, None));
+    }
+
+    #[test]
+    fn continues_from_offset() {
+        let meta = meta(Some("\"v1\""), Some(200));
+        assert!(continues(&meta, 100, &headers(&[(CONTENT_RANGE, "bytes 100-199/200")])));
+        assert!(continues(&meta, 100, &headers(&[(CONTENT_RANGE, "bytes 100-199/200"), (ETAG, "\"v1\"")])));
+        // 服务器没有告知总长度时不比较
+        assert!(continues(&meta, 100, &headers(&[(CONTENT_RANGE, "bytes 100-199/*")])));
+    }
+
+    #[test]
+    fn does_not_continue_changed_or_wrong_range() {
+        let meta = meta(Some("\"v1\""), Some(200));
+        assert!(!continues(&meta, 100, &headers(&[(CONTENT_RANGE, "bytes 0-199/200")])));
+        assert!(!continues(&meta, 100, &headers(&[(CONTENT_RANGE, "bytes 100-299/300")])));
+        assert!(!continues(&meta, 100, &headers(&[(CONTENT_RANGE, "bytes 100-199/200"), (ETAG, "\"v2\"")])));
+        assert!(!continues(&meta, 100, &HeaderMap::new()));
+    }
+}
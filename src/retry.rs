@@ -0,0 +1,165 @@
+//! 重试策略：指数退避 + 随机抖动，支持 `Retry-After`。
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+
+/// 重试策略，由 [`HttpClient`](crate::client::HttpClient) 在所有请求上统一使用。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最多尝试的次数（包括第一次），1 表示不重试。
+    pub max_attempts: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍。
+    pub base_delay: Duration,
+    /// 等待时间的上限；服务器要求的 `Retry-After` 超过它时不再重试。
+    pub max_delay: Duration,
+    /// 随机抖动的比例（0 ~ 1），实际等待时间在 `delay * (1 - jitter)` 到 `delay` 之间。
+    pub jitter: f64,
+    /// 是否重试网络错误（连接失败、连接被重置、下载不完整等）。
+    pub retry_network: bool,
+    /// 是否重试超时。
+    pub retry_timeout: bool,
+    /// 需要重试的 HTTP 状态码。
+    pub retry_statuses: Vec<u16>,
+    /// 是否遵守服务器返回的 `Retry-After`。
+    pub honor_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            retry_network: true,
+            retry_timeout: true,
+            retry_statuses: vec![408, 429, 500, 502, 503, 504],
+            honor_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试。
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    /// 第 `attempt` 次尝试（从 1 开始）失败后，是否需要重试，需要时返回等待时间。
+    pub fn next_delay(&self, attempt: u32, err: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let retry_after = match err {
+            Error::Network(_) | Error::Incomplete { .. } if self.retry_network => None,
+            Error::Timeout if self.retry_timeout => None,
+            Error::HttpStatus { status, retry_after } if self.retry_statuses.contains(&status.as_u16()) => {
+                *retry_after
+            }
+            _ => return None,
+        };
+
+        match retry_after.filter(|_| self.honor_retry_after) {
+            Some(delay) if delay > self.max_delay => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    // 指数退避：base_delay * 2^(attempt-1)，不超过 max_delay，再减去随机抖动
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 - jitter * fastrand::f64())
+    }
+}
+
+/// 解析 `Retry-After` 响应头（秒数，或者 HTTP 日期）。
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    // 没有随机抖动，方便比较等待时间
+    fn policy() -> RetryPolicy {
+        RetryPolicy { max_attempts: 5, base_delay: Duration::from_secs(1), jitter: 0.0, ..RetryPolicy::default() }
+    }
+
+    fn status(code: u16, retry_after: Option<u64>) -> Error {
+        Error::HttpStatus {
+            status: StatusCode::from_u16(code).unwrap(),
+            retry_after: retry_after.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_max_delay() {
+        let policy = policy();
+        let delays: Vec<_> = (1..5).map(|attempt| policy.next_delay(attempt, &Error::Timeout)).collect();
+        assert_eq!(delays, [1, 2, 4, 8].map(|secs| Some(Duration::from_secs(secs))));
+        let policy = RetryPolicy { max_attempts: 10, ..policy };
+        assert_eq!(policy.next_delay(9, &Error::Timeout), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn stops_after_max_attempts() {
+        let policy = policy();
+        assert_eq!(policy.next_delay(5, &Error::Timeout), None);
+        assert_eq!(RetryPolicy::none().next_delay(1, &Error::Timeout), None);
+    }
+
+    #[test]
+    fn retries_only_listed_statuses() {
+        let policy = policy();
+        assert!(policy.next_delay(1, &status(503, None)).is_some());
+        assert_eq!(policy.next_delay(1, &status(404, None)), None);
+        assert_eq!(policy.next_delay(1, &Error::Validate("不是 JSON".into())), None);
+        assert!(policy.next_delay(1, &Error::Incomplete { received: 1, expected: 2 }).is_some());
+        let policy = RetryPolicy { retry_timeout: false, retry_network: false, ..policy };
+        assert_eq!(policy.next_delay(1, &Error::Timeout), None);
+        assert_eq!(policy.next_delay(1, &Error::Incomplete { received: 1, expected: 2 }), None);
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let policy = policy();
+        assert_eq!(policy.next_delay(1, &status(429, Some(3))), Some(Duration::from_secs(3)));
+        // 超过 max_delay 时不再重试
+        assert_eq!(policy.next_delay(1, &status(429, Some(60))), None);
+        let policy = RetryPolicy { honor_retry_after: false, ..policy };
+        assert_eq!(policy.next_delay(2, &status(429, Some(60))), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn jitter_stays_within_range() {
+        let policy = RetryPolicy { jitter: 0.5, ..policy() };
+        for _ in 0..100 {
+            let delay = policy.next_delay(2, &Error::Timeout).unwrap();
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(" 120 "));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+    }
+}
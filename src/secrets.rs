@@ -0,0 +1,196 @@
+//! 密钥：清单中只写引用，实际的值来自环境变量或单独的密钥文件。
+//!
+//! - `${env:SUB_TOKEN}`：环境变量 `SUB_TOKEN`
+//! - `${secret:sub_password}`：密钥文件中的 `sub_password`
+//!
+//! 密钥文件是一层的键值对（JSON / YAML / TOML），不要提交到版本库中：
+//!
+//! ```yaml
+//! sub_password: hunter2
+//! private_token: glpat-xxxxxxxx
+//! ```
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::manifest::ManifestFormat;
+
+/// 对一个密钥的引用（`${env:名称}` 或 `${secret:名称}`），本身不包含密钥的值。
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SecretRef {
+    /// 环境变量。
+    Env(String),
+    /// 密钥文件中的一项。
+    File(String),
+}
+
+impl FromStr for SecretRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let reference = s.trim().strip_prefix("${").and_then(|rest| rest.strip_suffix('}'));
+        match reference.map(parse_reference) {
+            Some(Some(secret)) => Ok(secret),
+            _ => Err("密钥不能直接写在清单中，请写成 ${env:环境变量} 或 ${secret:密钥文件中的名称}".to_string()),
+        }
+    }
+}
+
+impl TryFrom<String> for SecretRef {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Env(name) => write!(f, "${{env:{}}}", name),
+            SecretRef::File(name) => write!(f, "${{secret:{}}}", name),
+        }
+    }
+}
+
+impl fmt::Debug for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// `env:名称` 或 `secret:名称`（不含 `${` 和 `}`）
+fn parse_reference(reference: &str) -> Option<SecretRef> {
+    let (kind, name) = reference.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    match kind.trim() {
+        "env" => Some(SecretRef::Env(name.to_string())),
+        "secret" => Some(SecretRef::File(name.to_string())),
+        _ => None,
+    }
+}
+
+/// 密钥文件中的所有密钥；没有密钥文件时为空，只能引用环境变量。
+#[derive(Default, Clone)]
+pub struct Secrets {
+    values: BTreeMap<String, String>,
+}
+
+// 不打印密钥的值
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Secrets").field("names", &self.values.keys().collect::<Vec<_>>()).finish()
+    }
+}
+
+impl Secrets {
+    /// 读取密钥文件（格式根据扩展名或内容判断）。
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::manifest(format!("读取密钥文件{}失败：{}", path.display(), err)))?;
+        let format = path
+            .to_str()
+            .and_then(ManifestFormat::from_path)
+            .unwrap_or_else(|| ManifestFormat::sniff(&content));
+        let Value::Object(map) = format.parse(&content)? else {
+            return Err(Error::manifest(format!("密钥文件{}的最外层必须是键值对", path.display())));
+        };
+        let values = map
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => Ok((name, value)),
+                _ => Err(Error::manifest(format!("密钥文件{}中的 {} 必须是字符串", path.display(), name))),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { values })
+    }
+
+    /// 取出引用的密钥的值。
+    pub fn resolve(&self, secret: &SecretRef) -> Result<String> {
+        match secret {
+            SecretRef::Env(name) => {
+                env::var(name).map_err(|_| Error::manifest(format!("环境变量 {} 没有设置", name)))
+            }
+            SecretRef::File(name) => self
+                .values
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::manifest(format!("密钥文件中没有 {}（是否指定了 --secrets？）", name))),
+        }
+    }
+
+    /// 把文本中的 `${env:名称}`、`${secret:名称}` 替换为密钥的值，其它内容原样保留。
+    pub fn expand(&self, text: &str) -> Result<String> {
+        let mut expanded = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            expanded.push_str(&rest[..start]);
+            let placeholder = &rest[start..start + len + 1];
+            match parse_reference(&placeholder[2..placeholder.len() - 1]) {
+                Some(secret) => expanded.push_str(&self.resolve(&secret)?),
+                None => expanded.push_str(placeholder),
+            }
+            rest = &rest[start + len + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> Secrets {
+        Secrets { values: BTreeMap::from([("password".to_string(), "hunter2".to_string())]) }
+    }
+
+    #[test]
+    fn parses_references() {
+        assert_eq!(" ${env:SUB_TOKEN} ".parse(), Ok(SecretRef::Env("SUB_TOKEN".to_string())));
+        assert_eq!("${secret: password }".parse(), Ok(SecretRef::File("password".to_string())));
+        assert_eq!(SecretRef::File("password".to_string()).to_string(), "${secret:password}");
+    }
+
+    #[test]
+    fn rejects_plain_values_and_bad_references() {
+        for text in ["hunter2", "${env:}", "${file:password}", "${env:SUB_TOKEN", "$env:SUB_TOKEN}"] {
+            assert!(text.parse::<SecretRef>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn expands_references_in_text() {
+        env::set_var("DLCONF_TEST_SECRETS_TOKEN", "abc");
+        let expanded = secrets().expand("https://x.example/${secret:password}?t=${env:DLCONF_TEST_SECRETS_TOKEN}");
+        assert_eq!(expanded.unwrap(), "https://x.example/hunter2?t=abc");
+    }
+
+    #[test]
+    fn keeps_other_placeholders() {
+        let secrets = secrets();
+        assert_eq!(secrets.expand("a ${other} b ${secret:password").unwrap(), "a ${other} b ${secret:password");
+        assert_eq!(secrets.expand("no secrets").unwrap(), "no secrets");
+    }
+
+    #[test]
+    fn fails_on_missing_secret() {
+        assert!(matches!(secrets().expand("${secret:missing}"), Err(Error::Manifest(_))));
+        assert!(matches!(secrets().expand("${env:DLCONF_TEST_SECRETS_UNSET}"), Err(Error::Manifest(_))));
+    }
+}
@@ -0,0 +1,203 @@
+//! 校验下载到的内容：按声明的格式解析，拒绝 HTML 错误页面和验证码页面，检查最基本的结构。
+//!
+//! 被拒绝的内容隔离到 `输出文件夹/.rejected/` 中，旁边的 `.txt` 文件记录链接和原因。
+
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::hash::content_hash;
+
+/// 隔离被拒绝内容的文件夹（在输出文件夹中）。
+pub const REJECTED_DIR: &str = ".rejected";
+
+// 判断是不是 HTML 页面时查看的字节数
+const SNIFF_LEN: usize = 64 * 1024;
+
+// 验证码 / 人机验证页面中常见的内容（小写）
+const CAPTCHA_MARKERS: &[&str] = &[
+    "captcha",
+    "cf-chl",
+    "challenge-platform",
+    "just a moment...",
+    "attention required! | cloudflare",
+    "verify you are human",
+    "人机验证",
+    "验证码",
+];
+
+/// 校验文本内容：不能为空，不能是 HTML 页面；JSON / YAML / TOML 必须能够解析，
+/// 并且是非空的键值对（或列表）。其它格式只检查前两项。
+pub fn validate_text(text: &str, format: &str) -> Result<()> {
+    check_not_html(text.as_bytes())?;
+    let format = format.trim().to_lowercase();
+    let structure = match format.as_str() {
+        "json" => {
+            let value: Value = serde_json::from_str(text).map_err(|err| invalid("JSON", err))?;
+            match value {
+                Value::Object(map) => Structure::Map(map.len()),
+                Value::Array(list) => Structure::List(list.len()),
+                _ => Structure::Scalar,
+            }
+        }
+        "yaml" | "yml" => {
+            let value: serde_yaml::Value = serde_yaml::from_str(text).map_err(|err| invalid("YAML", err))?;
+            match value {
+                serde_yaml::Value::Mapping(map) => Structure::Map(map.len()),
+                serde_yaml::Value::Sequence(list) => Structure::List(list.len()),
+                _ => Structure::Scalar,
+            }
+        }
+        "toml" => Structure::Map(text.parse::<toml::Table>().map_err(|err| invalid("TOML", err))?.len()),
+        _ => return Ok(()),
+    };
+    match structure {
+        Structure::Map(0) | Structure::List(0) => Err(Error::Validate(format!("{} 内容是空的", format))),
+        Structure::Scalar => Err(Error::Validate(format!("{} 内容不是键值对或列表", format))),
+        _ => Ok(()),
+    }
+}
+
+/// 校验下载好的二进制文件：不能为空，不能是 HTML 页面（只读取开头的一部分）。
+pub fn validate_binary_file(path: &Path) -> Result<()> {
+    let mut head = Vec::new();
+    File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    check_not_html(&head)
+}
+
+// 顶层结构，以及其中的项数
+enum Structure {
+    Map(usize),
+    List(usize),
+    Scalar,
+}
+
+fn invalid(format: &str, err: impl std::fmt::Display) -> Error {
+    Error::Validate(format!("不是合法的 {}（{}）", format, err))
+}
+
+// 内容为空，或者是 HTML 页面（错误页面、验证码页面）时返回错误
+fn check_not_html(body: &[u8]) -> Result<()> {
+    let head = &body[..body.len().min(SNIFF_LEN)];
+    let text = String::from_utf8_lossy(head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head)).to_lowercase();
+    let text = text.trim_start();
+    if text.is_empty() {
+        return Err(Error::Validate("内容为空".to_string()));
+    }
+    let is_html = ["<!doctype html", "<html", "<head", "<body"].iter().any(|tag| text.starts_with(tag));
+    if !is_html {
+        return Ok(());
+    }
+    if CAPTCHA_MARKERS.iter().any(|marker| text.contains(marker)) {
+        Err(Error::Validate("是验证码 / 人机验证页面".to_string()))
+    } else {
+        Err(Error::Validate("是 HTML 页面（可能是错误页面）".to_string()))
+    }
+}
+
+/// 把 `key` 下被拒绝的内容隔离到 `output/.rejected/` 中。
+#[derive(Debug, Clone, Copy)]
+pub struct Quarantine<'a> {
+    output: &'a str,
+    key: &'a str,
+}
+
+impl<'a> Quarantine<'a> {
+    pub fn new(output: &'a str, key: &'a str) -> Self {
+        Self { output, key }
+    }
+
+    /// 隔离（写入）被拒绝的内容 `body`。失败时只打印警告。
+    pub fn bytes(&self, url: &str, body: &[u8], reason: &Error) {
+        self.store(url, reason, |path| fs::write(path, body));
+    }
+
+    /// 隔离（移动）保存被拒绝内容的文件 `file`。失败时只打印警告，并删除 `file`。
+    pub fn file(&self, url: &str, file: &Path, reason: &Error) {
+        self.store(url, reason, |path| fs::rename(file, path));
+        let _ = fs::remove_file(file);
+    }
+
+    // 同一个链接被拒绝的内容只保留最近的一份：`key.链接哈希.body`，原因写在 `key.链接哈希.txt` 中
+    fn store(&self, url: &str, reason: &Error, write_body: impl FnOnce(&Path) -> io::Result<()>) {
+        let dir = Path::new(self.output).join(REJECTED_DIR);
+        let name = format!("{}.{}", self.key, &content_hash(url.as_bytes())[..8]);
+        let body_path = dir.join(format!("{}.body", name));
+        let result = fs::create_dir_all(&dir)
+            .and_then(|_| write_body(&body_path))
+            .and_then(|_| fs::write(dir.join(format!("{}.txt", name)), format!("链接：{}\n原因：{}\n", url, reason)));
+        match result {
+            Ok(()) => println!("  - {} 的内容已隔离到 {}", url, body_path.display()),
+            Err(err) => eprintln!("  - 隔离 {} 的内容失败：{}", url, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection(text: &str, format: &str) -> String {
+        match validate_text(text, format) {
+            Err(Error::Validate(reason)) => reason,
+            other => panic!("应该被拒绝：{:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_html_pages() {
+        let captcha = "<!DOCTYPE html>\n<html><head><title>Just a moment...</title></head></html>";
+        assert!(rejection(captcha, "yaml").contains("验证码"));
+        assert!(rejection("\u{FEFF}  <html><body>404 Not Found</body></html>", "json").contains("HTML"));
+        assert!(rejection(" \n", "txt").contains("内容为空"));
+        // 其它格式只检查不是 HTML 页面
+        assert!(validate_text("vmess://abc", "txt").is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_or_scalar_structures() {
+        assert!(rejection("[]", "json").contains("内容是空的"));
+        assert!(rejection("{}", "json").contains("内容是空的"));
+        assert!(rejection("null", "yaml").contains("不是键值对或列表"));
+        assert!(rejection("\"text\"", "json").contains("不是键值对或列表"));
+        assert!(rejection("{\"a\": ", "json").contains("不是合法的 JSON"));
+    }
+
+    #[test]
+    fn accepts_clash_config() {
+        let config = "\
+port: 7890
+mode: rule
+proxies:
+  - { name: a, type: ss, server: 1.2.3.4, port: 443, cipher: aes-128-gcm, password: x }
+proxy-groups:
+  - { name: auto, type: url-test, proxies: [a] }
+rules:
+  - MATCH,auto
+";
+        assert!(validate_text(config, "yaml").is_ok());
+        assert!(validate_text("[1]", "JSON").is_ok());
+        assert!(validate_text("a = 1", "toml").is_ok());
+    }
+
+    #[test]
+    fn quarantines_rejected_files() {
+        let output = std::env::temp_dir().join(format!("dlconf-validate-test-{}", std::process::id()));
+        fs::create_dir_all(&output).unwrap();
+        let file = output.join("download.tmp");
+        fs::write(&file, "<html>captcha</html>").unwrap();
+
+        let reason = validate_text(&fs::read_to_string(&file).unwrap(), "json").unwrap_err();
+        Quarantine::new(output.to_str().unwrap(), "clash").file("https://x.example/a.json", &file, &reason);
+
+        assert!(!file.exists());
+        let name = format!("clash.{}", &content_hash(b"https://x.example/a.json")[..8]);
+        let rejected = output.join(REJECTED_DIR);
+        assert_eq!(fs::read_to_string(rejected.join(format!("{}.body", name))).unwrap(), "<html>captcha</html>");
+        let note = fs::read_to_string(rejected.join(format!("{}.txt", name))).unwrap();
+        assert!(note.contains("https://x.example/a.json") && note.contains("验证码"), "{}", note);
+        fs::remove_dir_all(output).unwrap();
+    }
+}
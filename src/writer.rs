@@ -0,0 +1,328 @@
+//! 将数据写入输出文件夹。
+//!
+//! 输出文件都先写入同一文件夹中的临时文件，再重命名到位，读取输出文件夹的程序不会看到写了一半的文件；
+//! 内容没有变化的文件不重新写入，修改时间保持不变。
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::fetcher::url_file_name;
+use crate::hash::{content_hash, file_hash};
+use crate::numbering::Numbering;
+use crate::pipeline::UniqueContents;
+use crate::report::ItemReport;
+
+/// 写入一个输出文件的结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// 新建的文件。
+    Created,
+    /// 内容有变化，已经替换。
+    Updated,
+    /// 内容没有变化，没有重新写入。
+    Unchanged,
+    /// 不再使用，已经删除。
+    Removed,
+}
+
+impl WriteOutcome {
+    /// 文件是否有变化（新建、替换或删除）。
+    pub fn is_changed(self) -> bool {
+        self != WriteOutcome::Unchanged
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WriteOutcome::Created => "新建",
+            WriteOutcome::Updated => "更新",
+            WriteOutcome::Unchanged => "没有变化",
+            WriteOutcome::Removed => "删除",
+        }
+    }
+}
+
+/// 原子地写入文件：先写入同一文件夹中的临时文件，再重命名为 `path`。
+///
+/// `path` 已经存在并且内容（哈希）相同时不写入，保留原来的修改时间。
+pub fn write_atomic(path: impl AsRef<Path>, content: &[u8]) -> Result<WriteOutcome> {
+    let path = path.as_ref();
+    let existing = existing_hash(path)?;
+    if existing.as_deref() == Some(content_hash(content).as_str()) {
+        return Ok(WriteOutcome::Unchanged);
+    }
+    let temp_file = temp_path(path);
+    if let Err(err) = fs::write(&temp_file, content).and_then(|_| fs::rename(&temp_file, path)) {
+        let _ = fs::remove_file(&temp_file);
+        return Err(err.into());
+    }
+    Ok(if existing.is_some() { WriteOutcome::Updated } else { WriteOutcome::Created })
+}
+
+/// 与 [`write_atomic`] 相同，但内容来自（同一文件夹中的）临时文件 `temp_file`：
+/// 直接重命名为 `path`；内容没有变化时删除 `temp_file`。
+pub fn replace_file(path: impl AsRef<Path>, temp_file: &Path) -> Result<WriteOutcome> {
+    let path = path.as_ref();
+    let existing = existing_hash(path)?;
+    if existing.is_some() && existing == Some(file_hash(temp_file)?) {
+        fs::remove_file(temp_file)?;
+        return Ok(WriteOutcome::Unchanged);
+    }
+    fs::rename(temp_file, path)?;
+    Ok(if existing.is_some() { WriteOutcome::Updated } else { WriteOutcome::Created })
+}
+
+// 已有文件的内容哈希，文件不存在时为 `None`
+fn existing_hash(path: &Path) -> Result<Option<String>> {
+    match file_hash(path) {
+        Ok(hash) => Ok(Some(hash)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// 写入 `path` 时使用的临时文件：同一文件夹中的 `.文件名.tmp`
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+// 打印写入（或删除）文件的结果
+fn print_outcome(filename: &str, outcome: WriteOutcome) {
+    match outcome {
+        WriteOutcome::Unchanged => println!("  - 文件'{}'的内容没有变化，不重新写入", filename),
+        WriteOutcome::Removed => println!("  - 文件'{}'不再使用，已经删除", filename),
+        _ => println!("  - 数据已经写入文件'{}'", filename),
+    }
+}
+
+/// 目录不存在就创建文件夹。
+pub fn create_directory_if_not_exists(directory_path: &str) -> Result<()> {
+    let dir_path = Path::new(directory_path);
+    if !dir_path.exists() {
+        fs::create_dir_all(dir_path)?;
+    }
+    Ok(())
+}
+
+/// 将一个 key 下去重后的内容写入文件（不同的内容，用不同的文件存储）。
+///
+/// 只有一份内容时写入 `dir_name/inner_key.data_file`，多份时写入 `dir_name/inner_key_编号.data_file`，
+/// 编号由 `numbering` 决定（内容不变时编号不变）。文本原子地写入，二进制内容的临时文件移动（重命名）到位。
+///
+/// 之前写入、这次不再使用的文件（释放的编号，以及从多份变为一份时的编号文件，或者相反）会被删除，
+/// 下游程序不会读到过时的内容。每个文件的结果记录到 `report` 中。
+pub fn write_contents(
+    contents: &UniqueContents,
+    dir_name: &str,
+    inner_key: &str,
+    data_file: &str,
+    numbering: &Numbering,
+    report: &mut ItemReport,
+) -> Result<()> {
+    // 没有下载到任何内容时保留之前的文件和编号
+    if contents.is_empty() {
+        return Ok(());
+    }
+    let name = format!("{}.{}", inner_key, data_file);
+    let previous = numbering.numbers(&name);
+    let numbers = numbering.assign(&name, &contents.order);
+    let mut written = HashSet::new();
+    for (key, number) in contents.order.iter().zip(numbers) {
+        let filename = numbered_filename(dir_name, inner_key, (contents.len() > 1).then_some(number), data_file);
+        let outcome = match (contents.texts.get(key), contents.files.get(key)) {
+            (Some(content), _) => write_atomic(&filename, content)?,
+            (None, Some(temp_file)) => replace_file(&filename, temp_file)?,
+            (None, None) => continue,
+        };
+        print_outcome(&filename, outcome);
+        report.record_written(&filename, outcome);
+        written.insert(filename);
+    }
+
+    let stale = previous.into_iter().map(Some).chain([None]);
+    for filename in stale.map(|number| numbered_filename(dir_name, inner_key, number, data_file)) {
+        if written.contains(&filename) {
+            continue;
+        }
+        match fs::remove_file(&filename) {
+            Ok(()) => {
+                print_outcome(&filename, WriteOutcome::Removed);
+                report.record_written(&filename, WriteOutcome::Removed);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+// 没有编号时为 `dir_name/inner_key.data_file`，否则为 `dir_name/inner_key_编号.data_file`
+fn numbered_filename(dir_name: &str, inner_key: &str, number: Option<usize>, data_file: &str) -> String {
+    match number {
+        Some(number) => format!("{}/{}_{}.{}", dir_name, inner_key, number, data_file),
+        None => format!("{}/{}.{}", dir_name, inner_key, data_file),
+    }
+}
+
+/// 确定文件名（必要时添加编号），文件后缀截取于链接的后面。
+pub fn generate_unique_filename(url: &str, save_folder: &str) -> String {
+    let (filename, suffix) = split_file_name(url);
+
+    // 检查现有文件名，必要时添加编号
+    let mut count = 1;
+    let mut unique_file_name = format!("{}/{}_{}{}", save_folder, filename, count, suffix);
+    while Path::new(&unique_file_name).exists() {
+        count += 1;
+        unique_file_name = format!("{}/{}_{}{}", save_folder, filename, count, suffix);
+    }
+    unique_file_name
+}
+
+// 从 URL 提取文件名（不含查询参数），分割为文件名和扩展名（找不到扩展名时，直接在文件名后添加编号）
+fn split_file_name(url: &str) -> (&str, String) {
+    let original_file_name = url_file_name(url);
+    match original_file_name.split_once('.') {
+        Some((filename, suffix)) => (filename, format!(".{}", suffix)),
+        None => (original_file_name, String::new()),
+    }
+}
+
+/// 把（同一文件夹中的）临时文件 `temp_file` 保存为 [`generate_unique_filename`] 确定的文件，返回文件名。
+///
+/// 用硬链接占用文件名（文件名已经存在时失败），并发下载同名文件时不会互相覆盖，
+/// 也不会出现内容为空的文件；保存后删除 `temp_file`。
+pub fn store_unique_file(url: &str, save_folder: &str, temp_file: &Path) -> Result<String> {
+    loop {
+        let file_name = generate_unique_filename(url, save_folder);
+        match fs::hard_link(temp_file, &file_name) {
+            Ok(()) => {
+                fs::remove_file(temp_file)?;
+                return Ok(file_name);
+            }
+            // 文件名刚被其它下载占用，重新确定文件名
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// 在 `dir` 中创建（占用）一个新的临时文件 `.name.编号.tmp`，返回文件路径。
+///
+/// 用 `create_new` 占用文件名，并发的任务不会共用同一个临时文件。
+pub fn claim_temp_file(dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut count = 0;
+    loop {
+        let path = dir.join(format!(".{}.{}.tmp", name, count));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => count += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// 在 `save_folder` 中找出 [`generate_unique_filename`] 之前保存的、内容哈希为 `hash` 的文件。
+pub fn find_identical_file(url: &str, save_folder: &str, hash: &str) -> Result<Option<String>> {
+    let (filename, suffix) = split_file_name(url);
+    for count in 1.. {
+        let file_name = format!("{}/{}_{}{}", save_folder, filename, count, suffix);
+        match existing_hash(Path::new(&file_name))? {
+            Some(existing) if existing == hash => return Ok(Some(file_name)),
+            Some(_) => continue,
+            None => break,
+        }
+    }
+    Ok(None)
+}
+
+/// 将成功的链接保存到 `save_folder/valid_url.txt`。
+pub fn save_successful_urls(successful_urls: &[String], save_folder: &str) -> Result<()> {
+    let successful_url_path = format!("{}/valid_url.txt", save_folder);
+    write_atomic(successful_url_path, successful_urls.join("\n").as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    // 每个测试使用自己的空文件夹
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dlconf-writer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> =
+            fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_atomic_skips_unchanged_content() {
+        let dir = test_dir("atomic");
+        let path = dir.join("x.json");
+        assert_eq!(write_atomic(&path, b"a").unwrap(), WriteOutcome::Created);
+
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        File::options().write(true).open(&path).unwrap().set_modified(old).unwrap();
+        assert_eq!(write_atomic(&path, b"a").unwrap(), WriteOutcome::Unchanged);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), old);
+
+        assert_eq!(write_atomic(&path, b"b").unwrap(), WriteOutcome::Updated);
+        assert_eq!(fs::read(&path).unwrap(), b"b");
+        assert_eq!(file_names(&dir), ["x.json"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replace_file_moves_or_drops_temp_file() {
+        let dir = test_dir("replace");
+        let path = dir.join("geo.dat");
+        let write_temp = |content: &[u8]| {
+            let temp_file = claim_temp_file(&dir, "geo.dat").unwrap();
+            fs::write(&temp_file, content).unwrap();
+            temp_file
+        };
+        assert_eq!(replace_file(&path, &write_temp(b"a")).unwrap(), WriteOutcome::Created);
+        assert_eq!(replace_file(&path, &write_temp(b"a")).unwrap(), WriteOutcome::Unchanged);
+        assert_eq!(replace_file(&path, &write_temp(b"b")).unwrap(), WriteOutcome::Updated);
+        assert_eq!(fs::read(&path).unwrap(), b"b");
+        assert_eq!(file_names(&dir), ["geo.dat"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn claims_distinct_temp_files() {
+        let dir = test_dir("claim");
+        let first = claim_temp_file(&dir, "x.json").unwrap();
+        let second = claim_temp_file(&dir, "x.json").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(file_names(&dir), [".x.json.0.tmp", ".x.json.1.tmp"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stores_unique_files_without_empty_placeholders() {
+        let dir = test_dir("unique");
+        let folder = dir.to_str().unwrap();
+        let url = "https://x.example/geo.dat?v=1#top";
+        for content in [b"a", b"b"] {
+            let temp_file = claim_temp_file(&dir, "geo.dat").unwrap();
+            fs::write(&temp_file, content).unwrap();
+            store_unique_file(url, folder, &temp_file).unwrap();
+        }
+        assert_eq!(file_names(&dir), ["geo_1.dat", "geo_2.dat"]);
+        assert_eq!(fs::read(dir.join("geo_2.dat")).unwrap(), b"b");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}